@@ -12,14 +12,38 @@ use std::collections::HashMap;
 enum CustomTrapCode {
     /// Raised when shifting by a negative number
     NegativeShiftCount = 0,
+    /// Raised when raising an int to a negative power, since the Int-domain result would
+    /// need to become a float (not yet representable by a single JIT-compiled function).
+    NegativeIntPower = 1,
+    /// Raised by `//` or `%` when the divisor is zero.
+    ZeroDivision = 2,
 }
 
+/// The native width the JIT emits `Int` arithmetic in. Defaults to `i64`; enabling the `jit-i128`
+/// Cargo feature widens it to `i128` so that Python ints which fit in 128 but not 64 bits still
+/// get native-speed codegen instead of falling back to arbitrary precision -- gated the same way
+/// `num-traits` gates its own `i128` support, since not every target implements 128-bit integer
+/// ops as efficiently as 64-bit ones.
+#[cfg(not(feature = "jit-i128"))]
+const INT_WIDTH: Type = types::I64;
+#[cfg(feature = "jit-i128")]
+const INT_WIDTH: Type = types::I128;
+
 #[derive(Clone)]
 struct Local {
     var: Variable,
     ty: JitType,
 }
 
+/// Tracks where `break`/`continue` should jump to for one level of loop nesting.
+#[derive(Clone, Copy)]
+struct LoopBlock {
+    /// The loop header -- where `continue` jumps to re-check the loop condition.
+    head: Block,
+    /// Where the loop falls through to on `break` or when the condition becomes false.
+    exit: Block,
+}
+
 #[derive(Debug)]
 enum JitValue {
     Int(Value),
@@ -27,7 +51,10 @@ enum JitValue {
     Bool(Value),
     None,
     Tuple(Vec<JitValue>),
-    FuncRef(FuncRef),
+    /// A callable, along with the [`JitSig`] the compiler knows for it -- so a call through it can
+    /// validate the argument count/types it's given and push a result of the right [`JitType`]
+    /// instead of assuming `Int`.
+    FuncRef(FuncRef, JitSig),
 }
 
 impl JitValue {
@@ -44,23 +71,76 @@ impl JitValue {
             JitValue::Int(_) => Some(JitType::Int),
             JitValue::Float(_) => Some(JitType::Float),
             JitValue::Bool(_) => Some(JitType::Bool),
-            JitValue::None | JitValue::Tuple(_) | JitValue::FuncRef(_) => None,
+            JitValue::None | JitValue::Tuple(_) | JitValue::FuncRef(_, _) => None,
         }
     }
 
     fn into_value(self) -> Option<Value> {
         match self {
             JitValue::Int(val) | JitValue::Float(val) | JitValue::Bool(val) => Some(val),
-            JitValue::None | JitValue::Tuple(_) | JitValue::FuncRef(_) => None,
+            JitValue::None | JitValue::Tuple(_) | JitValue::FuncRef(_, _) => None,
+        }
+    }
+}
+
+/// A value known at compile time, kept alongside the operand stack so that arithmetic between two
+/// constants can be evaluated in Rust instead of emitting Cranelift IR for it. Mirrors the subset
+/// of [`JitValue`] that [`prepare_const`](FunctionCompiler::prepare_const) can already produce.
+#[derive(Debug, Clone, Copy)]
+enum Constant {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    None,
+}
+
+impl Constant {
+    fn to_jit_type(&self) -> Option<JitType> {
+        match self {
+            Constant::Int(_) => Some(JitType::Int),
+            Constant::Float(_) => Some(JitType::Float),
+            Constant::Bool(_) => Some(JitType::Bool),
+            Constant::None => None,
+        }
+    }
+}
+
+/// An operand-stack entry that might still be a compile-time [`Constant`] rather than a materialized
+/// Cranelift [`Value`]. Kept distinct from `JitValue` so that folding can be deferred: a constant
+/// only needs to become a real `iconst`/`f64const` instruction once a non-constant consumer (a
+/// `StoreFast`, a call argument, a mismatched-operand binary op, ...) actually needs the `Value`.
+#[derive(Debug, Clone)]
+enum StackValue {
+    Const(Constant),
+    Value(JitValue),
+}
+
+impl StackValue {
+    fn to_jit_type(&self) -> Option<JitType> {
+        match self {
+            StackValue::Const(c) => c.to_jit_type(),
+            StackValue::Value(v) => v.to_jit_type(),
+        }
+    }
+
+    fn as_const(&self) -> Option<Constant> {
+        match self {
+            StackValue::Const(c) => Some(*c),
+            StackValue::Value(_) => None,
         }
     }
 }
 
 pub struct FunctionCompiler<'a, 'b> {
     builder: &'a mut FunctionBuilder<'b>,
-    stack: Vec<JitValue>,
+    stack: Vec<StackValue>,
     variables: Box<[Option<Local>]>,
     label_to_block: HashMap<Label, Block>,
+    block_stack: Vec<LoopBlock>,
+    /// An imported `fn(f64, f64) -> f64` computing the general case of float `pow`, for
+    /// [`compile_fpow`](Self::compile_fpow) to fall back to once the IEEE-754/CPython special
+    /// cases it checks itself don't apply.
+    fpow: Option<FuncRef>,
     pub(crate) sig: JitSig,
 }
 
@@ -71,12 +151,27 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         arg_types: &[JitType],
         ret_type: Option<JitType>,
         entry_block: Block,
+    ) -> FunctionCompiler<'a, 'b> {
+        Self::with_fpow(builder, num_variables, arg_types, ret_type, entry_block, None)
+    }
+
+    /// Like [`new`](Self::new), but also lets the caller import a `pow` symbol for
+    /// [`compile_fpow`](Self::compile_fpow)'s general case.
+    pub fn with_fpow(
+        builder: &'a mut FunctionBuilder<'b>,
+        num_variables: usize,
+        arg_types: &[JitType],
+        ret_type: Option<JitType>,
+        entry_block: Block,
+        fpow: Option<FuncRef>,
     ) -> FunctionCompiler<'a, 'b> {
         let mut compiler = FunctionCompiler {
             builder,
             stack: Vec::new(),
             variables: vec![None; num_variables].into_boxed_slice(),
             label_to_block: HashMap::new(),
+            block_stack: Vec::new(),
+            fpow,
             sig: JitSig {
                 args: arg_types.to_vec(),
                 ret: ret_type,
@@ -91,11 +186,34 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         compiler
     }
 
-    fn pop_multiple(&mut self, count: usize) -> Vec<JitValue> {
+    fn pop_multiple(&mut self, count: usize) -> Vec<StackValue> {
         let stack_len = self.stack.len();
         self.stack.drain(stack_len - count..).collect()
     }
 
+    /// Turns a possibly-still-constant stack entry into a real `JitValue`, emitting the
+    /// `iconst`/`f64const`/`bint` instruction for it if it hadn't been materialized yet.
+    fn materialize(&mut self, val: StackValue) -> JitValue {
+        match val {
+            StackValue::Value(val) => val,
+            StackValue::Const(Constant::Int(val)) => {
+                // Every `Constant::Int` we construct has already been checked to fit in an i64,
+                // either because it came straight from `BorrowedConstant::Integer` or because
+                // `fold_binary`/`fold_unary` only produce one when the result fits. `int_const`
+                // widens it to the JIT's configured `INT_WIDTH`, so this doesn't produce an `I64`
+                // value that mismatches an `I128` operand it gets used alongside.
+                JitValue::Int(self.int_const(val as i64))
+            }
+            StackValue::Const(Constant::Float(val)) => {
+                JitValue::Float(self.builder.ins().f64const(val))
+            }
+            StackValue::Const(Constant::Bool(val)) => {
+                JitValue::Bool(self.builder.ins().iconst(types::I8, val as i64))
+            }
+            StackValue::Const(Constant::None) => JitValue::None,
+        }
+    }
+
     fn store_variable(
         &mut self,
         idx: bytecode::NameIdx,
@@ -128,13 +246,13 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 Ok(self.builder.ins().bint(types::I8, val))
             }
             JitValue::Int(val) => {
-                let zero = self.builder.ins().iconst(types::I64, 0);
+                let zero = self.int_const(0);
                 let val = self.builder.ins().icmp(IntCC::NotEqual, val, zero);
                 Ok(self.builder.ins().bint(types::I8, val))
             }
             JitValue::Bool(val) => Ok(val),
             JitValue::None => Ok(self.builder.ins().iconst(types::I8, 0)),
-            JitValue::Tuple(_) | JitValue::FuncRef(_) => Err(JitCompileError::NotSupported),
+            JitValue::Tuple(_) | JitValue::FuncRef(_, _) => Err(JitCompileError::NotSupported),
         }
     }
 
@@ -187,31 +305,121 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
     }
 
     fn prepare_const<C: bytecode::Constant>(
-        &mut self,
         constant: BorrowedConstant<'_, C>,
-    ) -> Result<JitValue, JitCompileError> {
+    ) -> Result<Constant, JitCompileError> {
         let value = match constant {
             BorrowedConstant::Integer { value } => {
-                let val = self.builder.ins().iconst(
-                    types::I64,
-                    value.to_i64().ok_or(JitCompileError::NotSupported)?,
-                );
-                JitValue::Int(val)
-            }
-            BorrowedConstant::Float { value } => {
-                let val = self.builder.ins().f64const(value);
-                JitValue::Float(val)
-            }
-            BorrowedConstant::Boolean { value } => {
-                let val = self.builder.ins().iconst(types::I8, value as i64);
-                JitValue::Bool(val)
+                Constant::Int(value.to_i64().ok_or(JitCompileError::NotSupported)? as i128)
             }
-            BorrowedConstant::None => JitValue::None,
+            BorrowedConstant::Float { value } => Constant::Float(value),
+            BorrowedConstant::Boolean { value } => Constant::Bool(value),
+            BorrowedConstant::None => Constant::None,
             _ => return Err(JitCompileError::NotSupported),
         };
         Ok(value)
     }
 
+    /// Evaluates `a <op> b` in Rust when both operands are known constants. Returns `None` (so the
+    /// caller falls back to emitting IR) whenever the runtime op would trap -- divide/mod by zero,
+    /// an overflowing result, or a negative integer exponent that would need a `float` result --
+    /// since folding must reproduce the trap, not silently swallow it.
+    fn fold_binary(op: BinaryOperator, a: Constant, b: Constant) -> Option<Constant> {
+        match (a, b) {
+            (Constant::Int(a), Constant::Int(b)) => Self::fold_int_binary(op, a, b),
+            (Constant::Float(a), Constant::Float(b)) => Self::fold_float_binary(op, a, b),
+            (Constant::Int(a), Constant::Float(b)) => Self::fold_float_binary(op, a as f64, b),
+            (Constant::Float(a), Constant::Int(b)) => Self::fold_float_binary(op, a, b as f64),
+            _ => None,
+        }
+    }
+
+    fn fold_int_binary(op: BinaryOperator, a: i128, b: i128) -> Option<Constant> {
+        let result = match op {
+            BinaryOperator::Add => a.checked_add(b)?,
+            BinaryOperator::Subtract => a.checked_sub(b)?,
+            BinaryOperator::Multiply => a.checked_mul(b)?,
+            // Same floor-division/modulo sign adjustment as `compile_ifloordiv`/`compile_imod`.
+            BinaryOperator::FloorDivide => {
+                if b == 0 {
+                    return None;
+                }
+                let q = a / b;
+                let r = a % b;
+                if r != 0 && (r < 0) != (b < 0) { q - 1 } else { q }
+            }
+            BinaryOperator::Modulo => {
+                if b == 0 {
+                    return None;
+                }
+                let r = a % b;
+                if r != 0 && (r < 0) != (b < 0) { r + b } else { r }
+            }
+            BinaryOperator::Divide => {
+                return Self::fold_float_binary(BinaryOperator::Divide, a as f64, b as f64);
+            }
+            _ => return None,
+        };
+        i64::try_from(result).ok().map(|_| Constant::Int(result))
+    }
+
+    fn fold_float_binary(op: BinaryOperator, a: f64, b: f64) -> Option<Constant> {
+        let result = match op {
+            BinaryOperator::Add => a + b,
+            BinaryOperator::Subtract => a - b,
+            BinaryOperator::Multiply => a * b,
+            BinaryOperator::Divide => a / b,
+            _ => return None,
+        };
+        Some(Constant::Float(result))
+    }
+
+    fn fold_unary(op: UnaryOperator, a: Constant) -> Option<Constant> {
+        match (op, a) {
+            (UnaryOperator::Minus, Constant::Int(a)) => {
+                let result = a.checked_neg()?;
+                i64::try_from(result).ok().map(|_| Constant::Int(result))
+            }
+            (UnaryOperator::Minus, Constant::Float(a)) => Some(Constant::Float(-a)),
+            (UnaryOperator::Plus, Constant::Int(a)) => Some(Constant::Int(a)),
+            (UnaryOperator::Plus, Constant::Float(a)) => Some(Constant::Float(a)),
+            (UnaryOperator::Not, a) => Some(Constant::Bool(!Self::truthy(a))),
+            _ => None,
+        }
+    }
+
+    fn truthy(a: Constant) -> bool {
+        match a {
+            Constant::Int(v) => v != 0,
+            Constant::Float(v) => v != 0.0,
+            Constant::Bool(v) => v,
+            Constant::None => false,
+        }
+    }
+
+    fn fold_compare(op: ComparisonOperator, a: Constant, b: Constant) -> Option<bool> {
+        match (a, b) {
+            (Constant::Int(a), Constant::Int(b)) => Some(Self::cmp(op, a, b)),
+            (Constant::Float(a), Constant::Float(b)) => Some(Self::cmp(op, a, b)),
+            (Constant::Int(a), Constant::Float(b)) => Some(Self::cmp(op, a as f64, b)),
+            (Constant::Float(a), Constant::Int(b)) => Some(Self::cmp(op, a, b as f64)),
+            (Constant::Bool(a), Constant::Bool(b)) => Some(Self::cmp(op, a as i128, b as i128)),
+            (Constant::Bool(a), Constant::Int(b)) => Some(Self::cmp(op, a as i128, b)),
+            (Constant::Int(a), Constant::Bool(b)) => Some(Self::cmp(op, a, b as i128)),
+            _ => None,
+        }
+    }
+
+    fn cmp<T: PartialOrd>(op: ComparisonOperator, a: T, b: T) -> bool {
+        match op {
+            ComparisonOperator::Equal => a == b,
+            ComparisonOperator::NotEqual => a != b,
+            ComparisonOperator::Less => a < b,
+            ComparisonOperator::LessOrEqual => a <= b,
+            ComparisonOperator::Greater => a > b,
+            ComparisonOperator::GreaterOrEqual => a >= b,
+        }
+    }
+
     fn return_value(&mut self, val: JitValue) -> Result<(), JitCompileError> {
         if let Some(ref ty) = self.sig.ret {
             if val.to_jit_type().as_ref() != Some(ty) {
@@ -241,6 +449,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
             Instruction::ExtendedArg => Ok(()),
             Instruction::JumpIfFalse { target } => {
                 let cond = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                let cond = self.materialize(cond);
 
                 let val = self.boolean_val(cond)?;
                 let then_block = self.get_or_create_block(target.get(arg));
@@ -254,6 +463,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
             }
             Instruction::JumpIfTrue { target } => {
                 let cond = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                let cond = self.materialize(cond);
 
                 let val = self.boolean_val(cond)?;
                 let then_block = self.get_or_create_block(target.get(arg));
@@ -275,29 +485,32 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 let local = self.variables[idx.get(arg) as usize]
                     .as_ref()
                     .ok_or(JitCompileError::BadBytecode)?;
-                self.stack.push(JitValue::from_type_and_value(
+                self.stack.push(StackValue::Value(JitValue::from_type_and_value(
                     local.ty.clone(),
                     self.builder.use_var(local.var),
-                ));
+                )));
                 Ok(())
             }
             Instruction::StoreFast(idx) => {
                 let val = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                let val = self.materialize(val);
                 self.store_variable(idx.get(arg), val)
             }
             Instruction::LoadConst { idx } => {
-                let val = self
-                    .prepare_const(bytecode.constants[idx.get(arg) as usize].borrow_constant())?;
-                self.stack.push(val);
+                let val =
+                    Self::prepare_const(bytecode.constants[idx.get(arg) as usize].borrow_constant())?;
+                self.stack.push(StackValue::Const(val));
                 Ok(())
             }
             Instruction::BuildTuple { size } => {
                 let elements = self.pop_multiple(size.get(arg) as usize);
-                self.stack.push(JitValue::Tuple(elements));
+                let elements = elements.into_iter().map(|e| self.materialize(e)).collect();
+                self.stack.push(StackValue::Value(JitValue::Tuple(elements)));
                 Ok(())
             }
             Instruction::UnpackSequence { size } => {
                 let val = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                let val = self.materialize(val);
 
                 let elements = match val {
                     JitValue::Tuple(elements) => elements,
@@ -308,16 +521,19 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                     return Err(JitCompileError::NotSupported);
                 }
 
-                self.stack.extend(elements.into_iter().rev());
+                self.stack
+                    .extend(elements.into_iter().rev().map(StackValue::Value));
                 Ok(())
             }
             Instruction::ReturnValue => {
                 let val = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                let val = self.materialize(val);
                 self.return_value(val)
             }
             Instruction::ReturnConst { idx } => {
-                let val = self
-                    .prepare_const(bytecode.constants[idx.get(arg) as usize].borrow_constant())?;
+                let val =
+                    Self::prepare_const(bytecode.constants[idx.get(arg) as usize].borrow_constant())?;
+                let val = self.materialize(StackValue::Const(val));
                 self.return_value(val)
             }
             Instruction::CompareOperation { op, .. } => {
@@ -326,6 +542,16 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 let b = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
                 let a = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
 
+                if let (Some(a), Some(b)) = (a.as_const(), b.as_const()) {
+                    if let Some(folded) = Self::fold_compare(op, a, b) {
+                        self.stack.push(StackValue::Const(Constant::Bool(folded)));
+                        return Ok(());
+                    }
+                }
+
+                let a = self.materialize(a);
+                let b = self.materialize(b);
+
                 let a_type: Option<JitType> = a.to_jit_type();
                 let b_type: Option<JitType> = b.to_jit_type();
 
@@ -355,8 +581,9 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
 
                         let val = self.builder.ins().icmp(cond, operand_one, operand_two);
                         // TODO: Remove this `bint` in cranelift 0.90 as icmp now returns i8
-                        self.stack
-                            .push(JitValue::Bool(self.builder.ins().bint(types::I8, val)));
+                        self.stack.push(StackValue::Value(JitValue::Bool(
+                            self.builder.ins().bint(types::I8, val),
+                        )));
                         Ok(())
                     }
                     (JitValue::Float(a), JitValue::Float(b)) => {
@@ -371,8 +598,9 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
 
                         let val = self.builder.ins().fcmp(cond, a, b);
                         // TODO: Remove this `bint` in cranelift 0.90 as fcmp now returns i8
-                        self.stack
-                            .push(JitValue::Bool(self.builder.ins().bint(types::I8, val)));
+                        self.stack.push(StackValue::Value(JitValue::Bool(
+                            self.builder.ins().bint(types::I8, val),
+                        )));
                         Ok(())
                     }
                     _ => Err(JitCompileError::NotSupported),
@@ -381,23 +609,32 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
             Instruction::UnaryOperation { op, .. } => {
                 let op = op.get(arg);
                 let a = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+
+                if let Some(a_const) = a.as_const() {
+                    if let Some(folded) = Self::fold_unary(op, a_const) {
+                        self.stack.push(StackValue::Const(folded));
+                        return Ok(());
+                    }
+                }
+
+                let a = self.materialize(a);
                 match (op, a) {
                     (UnaryOperator::Minus, JitValue::Int(val)) => {
                         // Compile minus as 0 - a.
-                        let zero = self.builder.ins().iconst(types::I64, 0);
-                        let out = self.compile_sub(zero, val);
-                        self.stack.push(JitValue::Int(out));
+                        let zero = self.int_const(0);
+                        let out = self.compile_isub(zero, val);
+                        self.stack.push(StackValue::Value(JitValue::Int(out)));
                         Ok(())
                     }
                     (UnaryOperator::Plus, JitValue::Int(val)) => {
                         // Nothing to do
-                        self.stack.push(JitValue::Int(val));
+                        self.stack.push(StackValue::Value(JitValue::Int(val)));
                         Ok(())
                     }
                     (UnaryOperator::Not, a) => {
                         let boolean = self.boolean_val(a)?;
                         let not_boolean = self.builder.ins().bxor_imm(boolean, 1);
-                        self.stack.push(JitValue::Bool(not_boolean));
+                        self.stack.push(StackValue::Value(JitValue::Bool(not_boolean)));
                         Ok(())
                     }
                     _ => Err(JitCompileError::NotSupported),
@@ -409,10 +646,51 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 let b = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
                 let a = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
 
+                if let (Some(a_const), Some(b_const)) = (a.as_const(), b.as_const()) {
+                    if let Some(folded) = Self::fold_binary(op, a_const, b_const) {
+                        self.stack.push(StackValue::Const(folded));
+                        return Ok(());
+                    }
+                }
+
+                // `x ** y` needs to know, before choosing which codegen path to emit, whether `y`
+                // can be negative -- the exact-`Int` squaring loop can't produce the `float` PEP
+                // result `** ` returns for a negative exponent, and a single compiled instruction
+                // can't emit a result that's sometimes `Int` and sometimes `Float` depending on a
+                // value only known at runtime. A literal exponent's sign is knowable here, before
+                // `materialize` erases that; a non-constant exponent's sign isn't, so that case
+                // bails out of JIT compilation entirely (see the `Power` arm below) rather than
+                // silently producing the wrong type or trapping.
+                let rhs_const_sign = match (&op, b.as_const()) {
+                    (BinaryOperator::Power, Some(Constant::Int(n))) => Some(n < 0),
+                    _ => None,
+                };
+
+                let a = self.materialize(a);
+                let b = self.materialize(b);
+
                 let a_type = a.to_jit_type();
                 let b_type = b.to_jit_type();
 
                 let val = match (op, a, b) {
+                    (BinaryOperator::Power, JitValue::Int(a), JitValue::Int(b))
+                        if rhs_const_sign == Some(true) =>
+                    {
+                        // Known-negative literal exponent (e.g. `x ** -1`): `compile_ipow_int`'s
+                        // domain can't represent this, so route to the float-producing loop, the
+                        // same one Python's own `int.__pow__` effectively falls back to.
+                        JitValue::Float(self.compile_ipow(a, b))
+                    }
+                    (BinaryOperator::Power, JitValue::Int(_), JitValue::Int(_))
+                        if rhs_const_sign.is_none() =>
+                    {
+                        // The exponent's sign isn't known until runtime, so neither a fixed `Int`
+                        // nor a fixed `Float` result type can be guaranteed ahead of time. Rather
+                        // than risk `compile_ipow_int` trapping on a negative value that should
+                        // have produced a `float`, bail out of JIT compilation for this bytecode
+                        // instruction; the interpreter handles it correctly instead.
+                        return Err(JitCompileError::NotSupported);
+                    }
                     (BinaryOperator::Add, JitValue::Int(a), JitValue::Int(b)) => {
                         let (out, carry) = self.builder.ins().iadd_ifcout(a, b);
                         self.builder.ins().trapif(
@@ -423,13 +701,13 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                         JitValue::Int(out)
                     }
                     (BinaryOperator::Subtract, JitValue::Int(a), JitValue::Int(b)) => {
-                        JitValue::Int(self.compile_sub(a, b))
+                        JitValue::Int(self.compile_isub(a, b))
                     }
                     (BinaryOperator::Multiply, JitValue::Int(a), JitValue::Int(b)) => {
                         JitValue::Int(self.builder.ins().imul(a, b))
                     }
                     (BinaryOperator::FloorDivide, JitValue::Int(a), JitValue::Int(b)) => {
-                        JitValue::Int(self.builder.ins().sdiv(a, b))
+                        JitValue::Int(self.compile_ifloordiv(a, b))
                     }
                     (BinaryOperator::Divide, JitValue::Int(a), JitValue::Int(b)) => {
                         // Convert to float for regular division
@@ -438,11 +716,10 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                         JitValue::Float(self.builder.ins().fdiv(a_float, b_float))
                     }
                     (BinaryOperator::Modulo, JitValue::Int(a), JitValue::Int(b)) => {
-                        JitValue::Int(self.builder.ins().srem(a, b))
+                        JitValue::Int(self.compile_imod(a, b))
                     }
-                    // Todo: This should return int when possible
                     (BinaryOperator::Power, JitValue::Int(a), JitValue::Int(b)) => {
-                        JitValue::Float(self.compile_ipow(a, b))
+                        JitValue::Int(self.compile_ipow_int(a, b))
                     }
                     (
                         BinaryOperator::Lshift | BinaryOperator::Rshift,
@@ -451,7 +728,10 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                     ) => {
                         // Shifts throw an exception if we have a negative shift count
                         // Remove all bits except the sign bit, and trap if its 1 (i.e. negative).
-                        let sign = self.builder.ins().ushr_imm(b, 63);
+                        let sign = self
+                            .builder
+                            .ins()
+                            .ushr_imm(b, (INT_WIDTH.bits() - 1) as i64);
                         self.builder.ins().trapnz(
                             sign,
                             TrapCode::User(CustomTrapCode::NegativeShiftCount as u16),
@@ -487,6 +767,9 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                     (BinaryOperator::Divide, JitValue::Float(a), JitValue::Float(b)) => {
                         JitValue::Float(self.builder.ins().fdiv(a, b))
                     }
+                    (BinaryOperator::Power, JitValue::Float(a), JitValue::Float(b)) => {
+                        JitValue::Float(self.compile_fpow(a, b)?)
+                    }
 
                     // Floats and Integers
                     (_, JitValue::Int(a), JitValue::Float(b))
@@ -514,17 +797,45 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                             BinaryOperator::Divide => {
                                 JitValue::Float(self.builder.ins().fdiv(operand_one, operand_two))
                             }
+                            BinaryOperator::Power => {
+                                JitValue::Float(self.compile_fpow(operand_one, operand_two)?)
+                            }
                             _ => return Err(JitCompileError::NotSupported),
                         }
                     }
+
                     _ => return Err(JitCompileError::NotSupported),
                 };
-                self.stack.push(val);
+                self.stack.push(StackValue::Value(val));
+
+                Ok(())
+            }
+            Instruction::SetupLoop { target } => {
+                let exit = self.get_or_create_block(target.get(arg));
+                let head = self.builder.create_block();
+                self.block_stack.push(LoopBlock { head, exit });
 
+                // Fall through into the loop header, same as the implicit jump the label
+                // handling in `compile` relies on for other blocks.
+                if !self.builder.is_filled() {
+                    self.builder.ins().jump(head, &[]);
+                }
+                self.builder.switch_to_block(head);
+
+                Ok(())
+            }
+            Instruction::PopBlock => {
+                self.block_stack.pop().ok_or(JitCompileError::BadBytecode)?;
                 Ok(())
             }
-            Instruction::SetupLoop { .. } | Instruction::PopBlock => {
-                // TODO: block support
+            Instruction::Break { .. } => {
+                let block = self.block_stack.last().ok_or(JitCompileError::BadBytecode)?;
+                self.builder.ins().jump(block.exit, &[]);
+                Ok(())
+            }
+            Instruction::Continue { .. } => {
+                let block = self.block_stack.last().ok_or(JitCompileError::BadBytecode)?;
+                self.builder.ins().jump(block.head, &[]);
                 Ok(())
             }
             Instruction::LoadGlobal(idx) => {
@@ -533,7 +844,17 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 if name.as_ref() != bytecode.obj_name.as_ref() {
                     Err(JitCompileError::NotSupported)
                 } else {
-                    self.stack.push(JitValue::FuncRef(func_ref));
+                    // The only callable this compiler can currently reach is the function it's
+                    // compiling itself (self-recursion), so its `JitSig` is `self.sig`. If
+                    // `self.sig.ret` is still unresolved here (no return type was given and no
+                    // `return` has been compiled yet), `CallFunctionPositional` re-reads it at call
+                    // time and rejects the call as `NotSupported` if it is still unresolved then.
+                    let sig = JitSig {
+                        args: self.sig.args.clone(),
+                        ret: self.sig.ret.clone(),
+                    };
+                    self.stack
+                        .push(StackValue::Value(JitValue::FuncRef(func_ref, sig)));
                     Ok(())
                 }
             }
@@ -541,16 +862,42 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
                 let nargs = nargs.get(arg);
 
                 let mut args = Vec::new();
+                let mut arg_types = Vec::new();
                 for _ in 0..nargs {
                     let arg = self.stack.pop().ok_or(JitCompileError::BadBytecode)?;
+                    let arg = self.materialize(arg);
+                    arg_types.push(arg.to_jit_type());
                     args.push(arg.into_value().unwrap());
                 }
 
                 match self.stack.pop().ok_or(JitCompileError::BadBytecode)? {
-                    JitValue::FuncRef(reference) => {
+                    StackValue::Value(JitValue::FuncRef(reference, sig)) => {
+                        // `args`/`arg_types` were collected by popping, so they're in reverse
+                        // call order; compare against the callee's parameters in that same order.
+                        if sig.args.len() != args.len() {
+                            return Err(JitCompileError::NotSupported);
+                        }
+                        for (expected, actual) in sig.args.iter().rev().zip(&arg_types) {
+                            if Some(expected.clone()) != *actual {
+                                return Err(JitCompileError::NotSupported);
+                            }
+                        }
+
+                        // `args` is still in the reverse (popped) order checked above; the actual
+                        // call needs it in real left-to-right call order.
+                        args.reverse();
                         let call = self.builder.ins().call(reference, &args);
                         let returns = self.builder.inst_results(call);
-                        self.stack.push(JitValue::Int(returns[0]));
+                        // Prefer the signature's own return type, but fall back to `self.sig.ret`
+                        // for the self-recursive case where it's only just been resolved.
+                        let ret_type = sig
+                            .ret
+                            .or_else(|| self.sig.ret.clone())
+                            .ok_or(JitCompileError::NotSupported)?;
+                        self.stack.push(StackValue::Value(JitValue::from_type_and_value(
+                            ret_type,
+                            returns[0],
+                        )));
 
                         Ok(())
                     }
@@ -561,20 +908,315 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         }
     }
 
-    fn compile_sub(&mut self, a: Value, b: Value) -> Value {
+    /// Builds an integer constant at the JIT's configured [`INT_WIDTH`]. Every literal this
+    /// builder passes (`0`, `1`, `-1`) fits in an `i64`, so callers always give one and it's
+    /// widened here if `INT_WIDTH` is wider than that.
+    fn int_const(&mut self, n: i64) -> Value {
+        let small = self.builder.ins().iconst(types::I64, n);
+        if INT_WIDTH == types::I64 {
+            small
+        } else {
+            self.builder.ins().sextend(INT_WIDTH, small)
+        }
+    }
+
+    /// The minimum representable value at [`INT_WIDTH`] (`i64::MIN` or `i128::MIN`) -- the one
+    /// value two's-complement negation can't produce a positive counterpart for.
+    fn int_min_const(&mut self) -> Value {
+        if INT_WIDTH == types::I64 {
+            self.int_const(i64::MIN)
+        } else {
+            // `i128::MIN` doesn't fit in the `i64` literal `int_const` takes, so build it
+            // directly from its low/high 64-bit halves instead.
+            let lo = self.builder.ins().iconst(types::I64, 0);
+            let hi = self.builder.ins().iconst(types::I64, i64::MIN);
+            self.builder.ins().iconcat(lo, hi)
+        }
+    }
+
+    /// Negates `v`, trapping `TrapCode::IntegerOverflow` for [`INT_WIDTH`]'s minimum value, whose
+    /// negation has no positive representation at that width -- plain `ineg` would silently wrap
+    /// back to the same minimum value instead.
+    fn compile_ineg(&mut self, v: Value) -> Value {
+        let int_min = self.int_min_const();
+        let is_min = self.builder.ins().icmp(IntCC::Equal, v, int_min);
+        self.builder
+            .ins()
+            .trapnz(is_min, TrapCode::IntegerOverflow);
+        self.builder.ins().ineg(v)
+    }
+
+    /// `abs(v)`, trapping `TrapCode::IntegerOverflow` at [`INT_WIDTH`]'s minimum value via
+    /// `compile_ineg` for the same reason `compile_ineg` does.
+    fn compile_iabs(&mut self, v: Value) -> Value {
+        let negated = self.compile_ineg(v);
+        let zero = self.int_const(0);
+        let is_neg = self.builder.ins().icmp(IntCC::SignedLessThan, v, zero);
+        self.builder.ins().select(is_neg, negated, v)
+    }
+
+    fn compile_isub(&mut self, a: Value, b: Value) -> Value {
         // TODO: this should be fine, but cranelift doesn't special-case isub_ifbout
         // let (out, carry) = self.builder.ins().isub_ifbout(a, b);
         // self.builder
         //     .ins()
         //     .trapif(IntCC::Overflow, carry, TrapCode::IntegerOverflow);
-        // TODO: this shouldn't wrap
-        let neg_b = self.builder.ins().ineg(b);
+        // `compile_ineg` (rather than a raw `ineg`) catches `a - i64::MIN`, whose negation would
+        // otherwise silently wrap instead of trapping.
+        let neg_b = self.compile_ineg(b);
         let (out, carry) = self.builder.ins().iadd_ifcout(a, neg_b);
         self.builder
             .ins()
             .trapif(IntCC::Overflow, carry, TrapCode::IntegerOverflow);
         out
     }
+    /// The sign-adjustment `sdiv`/`srem` need to go from C-style truncating division to Python's
+    /// floor division: whenever the remainder is non-zero and its sign disagrees with the
+    /// divisor's, the truncated quotient is one too high (for `//`) or the remainder needs `b`
+    /// added back in (for `%`). Both are computed with `select` so there's no extra branching.
+    fn div_mod_needs_adjust(&mut self, r: Value, b: Value) -> Value {
+        let zero = self.int_const(0);
+        let r_nonzero = self.builder.ins().icmp(IntCC::NotEqual, r, zero);
+        let r_neg = self.builder.ins().icmp(IntCC::SignedLessThan, r, zero);
+        let b_neg = self.builder.ins().icmp(IntCC::SignedLessThan, b, zero);
+        let signs_differ = self.builder.ins().bxor(r_neg, b_neg);
+        self.builder.ins().band(r_nonzero, signs_differ)
+    }
+
+    /// Traps before `sdiv`/`srem` see a divisor that would make them misbehave: `b == 0` (which
+    /// Cranelift traps on with the wrong, non-Python `TrapCode`) and `a == INT_WIDTH::MIN && b ==
+    /// -1` (whose mathematical result doesn't fit back in [`INT_WIDTH`], and which Cranelift
+    /// itself traps on as an integer overflow rather than returning a value).
+    fn trap_zero_and_overflowing_div(&mut self, a: Value, b: Value) {
+        let zero = self.int_const(0);
+        let b_is_zero = self.builder.ins().icmp(IntCC::Equal, b, zero);
+        self.builder.ins().trapnz(
+            b_is_zero,
+            TrapCode::User(CustomTrapCode::ZeroDivision as u16),
+        );
+
+        let int_min = self.int_min_const();
+        let a_is_min = self.builder.ins().icmp(IntCC::Equal, a, int_min);
+        let neg_one = self.int_const(-1);
+        let b_is_neg_one = self.builder.ins().icmp(IntCC::Equal, b, neg_one);
+        let overflows = self.builder.ins().band(a_is_min, b_is_neg_one);
+        self.builder
+            .ins()
+            .trapnz(overflows, TrapCode::IntegerOverflow);
+    }
+
+    fn compile_ifloordiv(&mut self, a: Value, b: Value) -> Value {
+        self.trap_zero_and_overflowing_div(a, b);
+        let q = self.builder.ins().sdiv(a, b);
+        let r = self.builder.ins().srem(a, b);
+        let needs_adjust = self.div_mod_needs_adjust(r, b);
+        let q_minus_one = self.builder.ins().iadd_imm(q, -1);
+        self.builder.ins().select(needs_adjust, q_minus_one, q)
+    }
+
+    fn compile_imod(&mut self, a: Value, b: Value) -> Value {
+        // Only a zero divisor traps here: `INT_WIDTH::MIN % -1` is `0`, which fits.
+        let zero = self.int_const(0);
+        let b_is_zero = self.builder.ins().icmp(IntCC::Equal, b, zero);
+        self.builder.ins().trapnz(
+            b_is_zero,
+            TrapCode::User(CustomTrapCode::ZeroDivision as u16),
+        );
+        let r = self.builder.ins().srem(a, b);
+        let needs_adjust = self.div_mod_needs_adjust(r, b);
+        let r_plus_b = self.builder.ins().iadd(r, b);
+        self.builder.ins().select(needs_adjust, r_plus_b, r)
+    }
+
+    /// Multiplies `a * b`, trapping `TrapCode::IntegerOverflow` if the true result doesn't fit
+    /// back in [`INT_WIDTH`]. Cranelift has no checked-multiply instruction, so this follows the
+    /// same trick `num-traits`' checked ops use at `i64` width: widen via `smulhi` for the high
+    /// half and compare it against the arithmetic sign-extension of the low half -- they agree
+    /// exactly when the product fits. At `i128` width, where Cranelift has no widening multiply
+    /// to get a true high half from, it round-trips through division instead (see below).
+    fn checked_imul(&mut self, a: Value, b: Value) -> Value {
+        let product = self.builder.ins().imul(a, b);
+        if INT_WIDTH == types::I64 {
+            // `smulhi` gives the high half of the true double-width product directly, so
+            // comparing it against the sign-extension of the low half (what it would be if there
+            // were no overflow) detects overflow in one pass -- mirrors the checked-multiply
+            // trick `num-traits` uses for fixed-width integers.
+            let hi = self.builder.ins().smulhi(a, b);
+            let sign = self.builder.ins().sshr_imm(product, 63);
+            let overflowed = self.builder.ins().icmp(IntCC::NotEqual, hi, sign);
+            self.builder
+                .ins()
+                .trapnz(overflowed, TrapCode::IntegerOverflow);
+        } else {
+            // `smulhi` would need a true double-width (I256) result to detect I128 overflow,
+            // which Cranelift doesn't have. Instead, round-trip the (possibly wrapped) product
+            // back through division: for any nonzero `a`, the multiply didn't overflow iff
+            // `product / a == b`. `a == 0` can never overflow (the product is trivially `0`), so
+            // it's excluded from the check rather than divided by.
+            let zero = self.int_const(0);
+            let one = self.int_const(1);
+            let a_is_zero = self.builder.ins().icmp(IntCC::Equal, a, zero);
+            let safe_divisor = self.builder.ins().select(a_is_zero, one, a);
+            let quotient = self.builder.ins().sdiv(product, safe_divisor);
+            let mismatch = self.builder.ins().icmp(IntCC::NotEqual, quotient, b);
+            let overflowed = self.builder.ins().band_not(mismatch, a_is_zero);
+            self.builder
+                .ins()
+                .trapnz(overflowed, TrapCode::IntegerOverflow);
+        }
+        product
+    }
+
+    /// Computes `a ** b` by squaring, staying entirely in the `Int` domain so the result doesn't
+    /// lose precision or type the way routing through `compile_ipow`'s float math does. `0 ** 0`
+    /// and `x ** 0` are both defined as `1` by the loop falling straight through to `exit_block`
+    /// with `result` still `1`; `0 ** positive` falls out naturally too, since squaring `0` stays
+    /// `0`. Every multiply is overflow-checked via [`checked_imul`](Self::checked_imul), and the
+    /// final iteration skips squaring `base` again (its squared value is never used for anything
+    /// but the next iteration's exponent check) so a result that fits in [`INT_WIDTH`] doesn't
+    /// spuriously trap on a squaring step the algorithm doesn't actually need. A negative `b`
+    /// would need a `float` result (Python returns e.g. `2 ** -1 == 0.5`), which this all-`Int`
+    /// signature can't produce -- the `Power` dispatch in `add_instruction` only ever calls this
+    /// with a `b` it has already proven non-negative (a non-negative literal exponent, since a
+    /// non-constant one bails out of JIT compilation instead of reaching here at all), so the
+    /// `NegativeIntPower` trap below should be unreachable; it's kept as a safety net in case that
+    /// invariant is ever violated.
+    fn compile_ipow_int(&mut self, a: Value, b: Value) -> Value {
+        let zero = self.int_const(0);
+        let is_neg = self.builder.ins().icmp(IntCC::SignedLessThan, b, zero);
+        self.builder.ins().trapnz(
+            is_neg,
+            TrapCode::User(CustomTrapCode::NegativeIntPower as u16),
+        );
+
+        let one = self.int_const(1);
+
+        let loop_block = self.builder.create_block();
+        let continue_block = self.builder.create_block();
+        let square_block = self.builder.create_block();
+        let rejoin_block = self.builder.create_block();
+        let exit_block = self.builder.create_block();
+
+        self.builder.append_block_param(loop_block, INT_WIDTH); // base
+        self.builder.append_block_param(loop_block, INT_WIDTH); // result
+        self.builder.append_block_param(loop_block, INT_WIDTH); // exponent
+
+        self.builder.append_block_param(continue_block, INT_WIDTH);
+        self.builder.append_block_param(continue_block, INT_WIDTH);
+        self.builder.append_block_param(continue_block, INT_WIDTH);
+
+        self.builder.append_block_param(rejoin_block, INT_WIDTH); // base to continue with
+        self.builder.append_block_param(rejoin_block, INT_WIDTH); // result
+        self.builder.append_block_param(rejoin_block, INT_WIDTH); // exponent
+
+        self.builder.append_block_param(exit_block, INT_WIDTH);
+
+        self.builder.ins().jump(loop_block, &[a, one, b]);
+
+        // loop_block: while exponent > 0, keep squaring; otherwise we're done.
+        self.builder.switch_to_block(loop_block);
+        let params = self.builder.block_params(loop_block);
+        let (base_lb, result_lb, exp_lb) = (params[0], params[1], params[2]);
+        let zero = self.int_const(0);
+        let is_zero = self.builder.ins().icmp(IntCC::Equal, exp_lb, zero);
+        self.builder.ins().brnz(is_zero, exit_block, &[result_lb]);
+        self.builder
+            .ins()
+            .jump(continue_block, &[base_lb, result_lb, exp_lb]);
+
+        // continue_block: result *= base (if the low bit of exponent is set), exponent >>= 1.
+        self.builder.switch_to_block(continue_block);
+        let params = self.builder.block_params(continue_block);
+        let (base_cb, result_cb, exp_cb) = (params[0], params[1], params[2]);
+        let is_odd = self.builder.ins().band_imm(exp_cb, 1);
+        let is_odd = self.builder.ins().icmp_imm(IntCC::Equal, is_odd, 1);
+        let mul_result = self.checked_imul(result_cb, base_cb);
+        let new_result = self.builder.ins().select(is_odd, mul_result, result_cb);
+        let new_exp = self.builder.ins().ushr_imm(exp_cb, 1);
+
+        // base *= base, unless this was the last iteration (new_exp == 0), in which case the
+        // squared value would only ever be checked for overflow and then discarded.
+        let exp_done = self.builder.ins().icmp(IntCC::Equal, new_exp, zero);
+        self.builder.ins().brnz(exp_done, rejoin_block, &[base_cb, new_result, new_exp]);
+        self.builder.ins().jump(square_block, &[]);
+
+        self.builder.switch_to_block(square_block);
+        let squared_base = self.checked_imul(base_cb, base_cb);
+        self.builder
+            .ins()
+            .jump(rejoin_block, &[squared_base, new_result, new_exp]);
+
+        self.builder.switch_to_block(rejoin_block);
+        let params = self.builder.block_params(rejoin_block);
+        let (base_rb, result_rb, exp_rb) = (params[0], params[1], params[2]);
+        self.builder
+            .ins()
+            .jump(loop_block, &[base_rb, result_rb, exp_rb]);
+
+        self.builder.switch_to_block(exit_block);
+        let result = self.builder.block_params(exit_block)[0];
+
+        self.builder.seal_block(loop_block);
+        self.builder.seal_block(continue_block);
+        self.builder.seal_block(square_block);
+        self.builder.seal_block(rejoin_block);
+        self.builder.seal_block(exit_block);
+
+        result
+    }
+
+    /// Computes `base ** exp` for `float` operands. CPython's own `float.__pow__` doesn't trust
+    /// the platform libm's `pow` to get the IEEE-754 special cases right everywhere, so it checks
+    /// them itself first; this mirrors that table (branch-free, via `select`, since every case
+    /// here is a plain value rather than something with side effects to skip) before falling back
+    /// to calling the imported `pow` [`FuncRef`] for the general case. NaN-base and signed-zero
+    /// results are left to that general call rather than special-cased again here, since a
+    /// conforming C99 `pow` already gets those right.
+    fn compile_fpow(&mut self, base: Value, exp: Value) -> Result<Value, JitCompileError> {
+        let fpow = self.fpow.ok_or(JitCompileError::NotSupported)?;
+        let call = self.builder.ins().call(fpow, &[base, exp]);
+        let general = self.builder.inst_results(call)[0];
+
+        let zero = self.builder.ins().f64const(0.0);
+        let one = self.builder.ins().f64const(1.0);
+        let neg_one = self.builder.ins().f64const(-1.0);
+        let pos_inf = self.builder.ins().f64const(f64::INFINITY);
+        let neg_inf = self.builder.ins().f64const(f64::NEG_INFINITY);
+        let abs_base = self.builder.ins().fabs(base);
+
+        // anything ** 0.0 -> 1.0 (including a NaN base); 1.0 ** anything -> 1.0 (including a NaN
+        // exponent).
+        let exp_is_zero = self.builder.ins().fcmp(FloatCC::Equal, exp, zero);
+        let base_is_one = self.builder.ins().fcmp(FloatCC::Equal, base, one);
+        let trivial_one = self.builder.ins().bor(exp_is_zero, base_is_one);
+
+        // |x| > 1 ** +inf -> +inf, ** -inf -> +0; |x| < 1 ** +inf -> +0, ** -inf -> +inf.
+        let exp_is_pos_inf = self.builder.ins().fcmp(FloatCC::Equal, exp, pos_inf);
+        let exp_is_neg_inf = self.builder.ins().fcmp(FloatCC::Equal, exp, neg_inf);
+        let exp_is_inf = self.builder.ins().bor(exp_is_pos_inf, exp_is_neg_inf);
+        // A NaN base stays NaN for an infinite exponent, so it's left to the general call.
+        let base_is_nan = self.builder.ins().fcmp(FloatCC::Unordered, base, base);
+        let exp_is_inf = self.builder.ins().band_not(exp_is_inf, base_is_nan);
+        let base_magnitude_gt_one = self.builder.ins().fcmp(FloatCC::GreaterThan, abs_base, one);
+        let gt_one_result = self.builder.ins().select(exp_is_pos_inf, pos_inf, zero);
+        let lt_one_result = self.builder.ins().select(exp_is_pos_inf, zero, pos_inf);
+        let magnitude_inf_result =
+            self.builder
+                .ins()
+                .select(base_magnitude_gt_one, gt_one_result, lt_one_result);
+
+        // (-1.0) ** +-inf -> 1.0, overriding the magnitude-based result just computed.
+        let base_is_neg_one = self.builder.ins().fcmp(FloatCC::Equal, base, neg_one);
+        let inf_result = self
+            .builder
+            .ins()
+            .select(base_is_neg_one, one, magnitude_inf_result);
+
+        // Priority: trivial_one, then the infinite-exponent cases, then the general call.
+        let with_inf = self.builder.ins().select(exp_is_inf, inf_result, general);
+        Ok(self.builder.ins().select(trivial_one, one, with_inf))
+    }
+
     fn compile_ipow(&mut self, a: Value, b: Value) -> Value {
         // Convert base to float since result might not always be a Int
         let float_base = self.builder.ins().fcvt_from_sint(types::F64, a);
@@ -591,24 +1233,24 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         // Set code block params
         // Set code block params
         self.builder.append_block_param(check_block1, types::F64);
-        self.builder.append_block_param(check_block1, types::I64);
+        self.builder.append_block_param(check_block1, INT_WIDTH);
 
         self.builder.append_block_param(check_block2, types::F64);
-        self.builder.append_block_param(check_block2, types::I64);
+        self.builder.append_block_param(check_block2, INT_WIDTH);
 
         self.builder.append_block_param(check_block3, types::F64);
-        self.builder.append_block_param(check_block3, types::I64);
+        self.builder.append_block_param(check_block3, INT_WIDTH);
 
         self.builder.append_block_param(handle_neg_exp, types::F64);
-        self.builder.append_block_param(handle_neg_exp, types::I64);
+        self.builder.append_block_param(handle_neg_exp, INT_WIDTH);
 
         self.builder.append_block_param(loop_block, types::F64); //base
         self.builder.append_block_param(loop_block, types::F64); //result
-        self.builder.append_block_param(loop_block, types::I64); //exponent
+        self.builder.append_block_param(loop_block, INT_WIDTH); //exponent
 
         self.builder.append_block_param(continue_block, types::F64); //base
         self.builder.append_block_param(continue_block, types::F64); //result
-        self.builder.append_block_param(continue_block, types::I64); //exponent
+        self.builder.append_block_param(continue_block, INT_WIDTH); //exponent
 
         self.builder.append_block_param(exit_block, types::F64);
 
@@ -623,7 +1265,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         let basec1 = paramsc1[0];
         let expc1 = paramsc1[1];
         let zero_f64 = self.builder.ins().f64const(0.0);
-        let zero_i64 = self.builder.ins().iconst(types::I64, 0);
+        let zero_i64 = self.int_const(0);
         let is_base_zero = self.builder.ins().fcmp(FloatCC::Equal, zero_f64, basec1);
         let is_exp_positive = self
             .builder
@@ -642,7 +1284,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         let paramsc2 = self.builder.block_params(check_block2);
         let basec2 = paramsc2[0];
         let expc2 = paramsc2[1];
-        let zero_i64 = self.builder.ins().iconst(types::I64, 0);
+        let zero_i64 = self.int_const(0);
         let is_neg = self
             .builder
             .ins()
@@ -660,7 +1302,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         let basec3 = paramsc3[0];
         let expc3 = paramsc3[1];
         let resc3 = self.builder.ins().f64const(1.0);
-        let one_i64 = self.builder.ins().iconst(types::I64, 1);
+        let one_i64 = self.int_const(1);
         let is_one = self.builder.ins().icmp(IntCC::Equal, expc3, one_i64);
         self.builder.ins().brnz(is_one, exit_block, &[basec3]);
         self.builder.ins().jump(loop_block, &[basec3, resc3, expc3]);
@@ -687,7 +1329,7 @@ impl<'a, 'b> FunctionCompiler<'a, 'b> {
         let baselb = paramslb[0];
         let reslb = paramslb[1];
         let explb = paramslb[2];
-        let zero = self.builder.ins().iconst(types::I64, 0);
+        let zero = self.int_const(0);
         let is_zero = self.builder.ins().icmp(IntCC::Equal, explb, zero);
         self.builder.ins().brnz(is_zero, exit_block, &[reslb]);
         self.builder
@@ -0,0 +1,120 @@
+//! Compiles a directory tree of `.py` files into frozen bytecode, so that an embedder can bake
+//! a whole application's modules into their binary the same way `py_freeze!`/`add_frozen` do for
+//! a single inline string of source.
+
+use rustpython_vm::{Interpreter, bytecode::CodeObject, compiler::Mode};
+use std::{fs, io, path::Path};
+
+struct FrozenEntry {
+    /// Dotted module name, e.g. `my_pkg.sub_mod`.
+    module_name: String,
+    is_package: bool,
+    code: CodeObject,
+}
+
+/// Recursively compiles every `.py` file under `input_dir` and writes the result to `output`.
+///
+/// If `output` ends in `.rs`, the frozen modules are emitted as a
+/// `pub static FROZEN_MODULES: &[(&str, &[u8], bool)]` slice of
+/// `(module_name, marshalled_code, is_package)` tuples suitable for passing to
+/// [`VirtualMachine::add_frozen`](rustpython_vm::VirtualMachine::add_frozen) via
+/// `rustpython_vm::frozen::FrozenModule::decode`. Otherwise a single marshalled blob is written
+/// that a loader can read back with the same decoding step.
+pub fn freeze_dir(input_dir: &str, output: &str) -> io::Result<()> {
+    let interp = Interpreter::without_stdlib(Default::default());
+    let entries = interp.enter(|vm| collect_entries(vm, Path::new(input_dir), ""))?;
+
+    if output.ends_with(".rs") {
+        write_rs(output, &entries)
+    } else {
+        write_blob(output, &entries)
+    }
+}
+
+fn collect_entries(
+    vm: &rustpython_vm::VirtualMachine,
+    dir: &Path,
+    prefix: &str,
+) -> io::Result<Vec<FrozenEntry>> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        if path.is_dir() {
+            let name = module_name(&path, prefix);
+            entries.extend(collect_entries(vm, &path, &name)?);
+            continue;
+        }
+        if path.extension().and_then(|e| e.to_str()) != Some("py") {
+            continue;
+        }
+        let is_package = path.file_stem().and_then(|s| s.to_str()) == Some("__init__");
+        let module_name = if is_package {
+            if prefix.is_empty() {
+                // `dir` is `input_dir` itself (the root of the tree being frozen) and it's a
+                // package in its own right -- there's no parent prefix to fall back on, so name
+                // it after its own directory, the same way a nested package would be named after
+                // its directory by the `module_name(&path, prefix)` call above.
+                dir.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(str::to_owned)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "input_dir has no usable directory name to freeze as a root package",
+                        )
+                    })?
+            } else {
+                prefix.to_owned()
+            }
+        } else {
+            module_name(&path, prefix)
+        };
+        let source = fs::read_to_string(&path)?;
+        let code = vm
+            .compile(&source, Mode::Exec, path.display().to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        entries.push(FrozenEntry {
+            module_name,
+            is_package,
+            code: code.into(),
+        });
+    }
+    Ok(entries)
+}
+
+fn module_name(path: &Path, prefix: &str) -> String {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    if prefix.is_empty() {
+        stem.to_owned()
+    } else {
+        format!("{prefix}.{stem}")
+    }
+}
+
+fn write_rs(output: &str, entries: &[FrozenEntry]) -> io::Result<()> {
+    let mut out = String::from("// @generated by `rustpython --freeze`\n");
+    out.push_str("pub static FROZEN_MODULES: &[(&str, &[u8], bool)] = &[\n");
+    for entry in entries {
+        let bytes = entry.code.marshal();
+        out.push_str(&format!(
+            "    ({:?}, &{:?}, {}),\n",
+            entry.module_name, bytes, entry.is_package
+        ));
+    }
+    out.push_str("];\n");
+    fs::write(output, out)
+}
+
+fn write_blob(output: &str, entries: &[FrozenEntry]) -> io::Result<()> {
+    let mut blob = Vec::new();
+    for entry in entries {
+        let bytes = entry.code.marshal();
+        blob.extend((entry.module_name.len() as u32).to_le_bytes());
+        blob.extend(entry.module_name.as_bytes());
+        blob.push(entry.is_package as u8);
+        blob.extend((bytes.len() as u32).to_le_bytes());
+        blob.extend(bytes);
+    }
+    fs::write(output, blob)
+}
@@ -0,0 +1,74 @@
+use rustpython_vm::{Interpreter, Settings, VirtualMachine};
+
+type InitHook = Box<dyn FnOnce(&mut VirtualMachine)>;
+
+/// A builder that lets you customize how an [`Interpreter`] is initialized before constructing
+/// one, e.g. to register native modules with [`VirtualMachine::add_native_module`] or to add
+/// frozen modules with [`VirtualMachine::add_frozen`].
+#[derive(Default)]
+pub struct InterpreterConfig {
+    settings: Option<Settings>,
+    init_hooks: Vec<InitHook>,
+}
+
+impl InterpreterConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn interpreter(self) -> Interpreter {
+        let settings = self.settings.unwrap_or_default();
+        let init = move |vm: &mut VirtualMachine| {
+            for hook in self.init_hooks {
+                hook(vm);
+            }
+        };
+        Interpreter::with_init(settings, init)
+    }
+
+    #[must_use]
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    #[must_use]
+    pub fn init_hook(mut self, hook: InitHook) -> Self {
+        self.init_hooks.push(hook);
+        self
+    }
+
+    #[must_use]
+    #[cfg(feature = "stdlib")]
+    pub fn init_stdlib(self) -> Self {
+        self.init_hook(Box::new(|vm| {
+            vm.add_native_modules(rustpython_stdlib::get_module_inits());
+        }))
+    }
+}
+
+/// Runs each of `scripts` in its own freshly built [`Interpreter`], so that no `sys.modules`
+/// entry, builtin, or other global VM state leaks from one script into the next.
+///
+/// `make_config` is called once per script to build that subinterpreter's [`InterpreterConfig`];
+/// it's a factory rather than a single `InterpreterConfig` because native modules registered via
+/// [`InterpreterConfig::init_hook`]/[`init_stdlib`](InterpreterConfig::init_stdlib) must be
+/// re-registered for every subinterpreter, and because the hooks are consumed (`FnOnce`) the
+/// first time an `InterpreterConfig` is turned into an `Interpreter`.
+///
+/// Note that a [`PyObjectRef`](rustpython_vm::PyObjectRef) created in one subinterpreter must
+/// never be stored (e.g. in a Rust `static`) and used from another: each subinterpreter has its
+/// own heap, and crossing that boundary is unsound.
+pub fn run_isolated(
+    make_config: impl Fn() -> InterpreterConfig,
+    scripts: &[String],
+    run_script: impl Fn(&VirtualMachine, &str) -> rustpython_vm::PyResult<()>,
+) -> Vec<u8> {
+    scripts
+        .iter()
+        .map(|script| {
+            let interp = make_config().interpreter();
+            interp.run(|vm| run_script(vm, script))
+        })
+        .collect()
+}
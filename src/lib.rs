@@ -45,7 +45,10 @@ extern crate log;
 #[cfg(feature = "flame-it")]
 use vm::Settings;
 
+mod freeze;
 mod interpreter;
+#[cfg(feature = "flame-it")]
+mod sample_profiler;
 mod settings;
 mod shell;
 
@@ -61,7 +64,7 @@ pub use shell::run_shell;
 
 /// The main cli of the `rustpython` interpreter. This function will return `std::process::ExitCode`
 /// based on the return code of the python code ran through the cli.
-pub fn run(init: impl FnOnce(&mut VirtualMachine) + 'static) -> ExitCode {
+pub fn run(init: impl Fn(&mut VirtualMachine) + Clone + 'static) -> ExitCode {
     env_logger::init();
 
     // NOTE: This is not a WASI convention. But it will be convenient since POSIX shell always defines it.
@@ -93,6 +96,39 @@ pub fn run(init: impl FnOnce(&mut VirtualMachine) + 'static) -> ExitCode {
         }
     }
 
+    let run_mode = match run_mode {
+        RunMode::Freeze { input_dir, output } => {
+            return match freeze::freeze_dir(&input_dir, &output) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    println!("{e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        RunMode::IsolatedScripts(scripts) => {
+            let exitcodes = interpreter::run_isolated(
+                || {
+                    let init = init.clone();
+                    let mut config = InterpreterConfig::new().settings(settings.clone());
+                    #[cfg(feature = "stdlib")]
+                    {
+                        config = config.init_stdlib();
+                    }
+                    config.init_hook(Box::new(init))
+                },
+                &scripts,
+                |vm, script| {
+                    let scope = setup_main_module(vm)?;
+                    vm.run_script(scope, script)
+                },
+            );
+            let exitcode = exitcodes.into_iter().find(|&c| c != 0).unwrap_or(0);
+            return ExitCode::from(exitcode);
+        }
+        run_mode => run_mode,
+    };
+
     let mut config = InterpreterConfig::new().settings(settings);
     #[cfg(feature = "stdlib")]
     {
@@ -140,7 +176,34 @@ __import__("io").TextIOWrapper(
     Ok(())
 }
 
+/// Installs pip from a local wheel by unpacking it straight into `site-packages`, mirroring
+/// how the maturin/wheel toolchain installs from prebuilt artifacts. The file is opened as a zip
+/// archive and extracted without a build step, so an sdist is not installed. Requires no network
+/// access and no `ssl` feature, so air-gapped and `--no-default-features` builds can still
+/// bootstrap pip.
+fn install_pip_from_wheel(path: &str, scope: Scope, vm: &VirtualMachine) -> PyResult<()> {
+    let install_wheel = rustpython_vm::py_compile!(
+        source = r#"\
+import zipfile, sysconfig
+with zipfile.ZipFile(__wheel_path__) as wheel:
+    wheel.extractall(sysconfig.get_path("purelib"))
+"#,
+        mode = "exec"
+    );
+    let installer_scope = vm.new_scope_with_builtins();
+    installer_scope
+        .globals
+        .set_item("__wheel_path__", vm.ctx.new_str(path).into(), vm)?;
+    vm.run_code_obj(vm.ctx.new_code(install_wheel), installer_scope)?;
+    let _ = scope;
+    Ok(())
+}
+
 fn install_pip(installer: InstallPipMode, scope: Scope, vm: &VirtualMachine) -> PyResult<()> {
+    if let InstallPipMode::Wheel { path } = &installer {
+        return install_pip_from_wheel(path, scope, vm);
+    }
+
     if cfg!(not(feature = "ssl")) {
         return Err(vm.new_exception_msg(
             vm.ctx.exceptions.system_error.to_owned(),
@@ -151,12 +214,61 @@ fn install_pip(installer: InstallPipMode, scope: Scope, vm: &VirtualMachine) ->
     match installer {
         InstallPipMode::Ensurepip => vm.run_module("ensurepip"),
         InstallPipMode::GetPip => get_pip(scope, vm),
+        InstallPipMode::Wheel { .. } => unreachable!("handled above"),
     }
 }
 
+/// Whether this build has real OS threads to back the `threading` module. `std::thread::spawn`
+/// panics at runtime on `wasm32-unknown-unknown` (no threads proposal there); every other target
+/// RustPython builds for, including `wasm32-wasi`, has real threads.
+fn threading_available() -> bool {
+    !cfg!(all(target_arch = "wasm32", target_os = "unknown"))
+}
+
+/// Builds the JSON blob printed by `--sysconfig`, describing this interpreter's version, enabled
+/// Cargo features, target triple, and stdlib location, mirroring what the PyO3/python3-sys build
+/// scripts parse out of CPython's `py_sys_config`.
+fn sysconfig_json() -> String {
+    let features = [
+        (cfg!(feature = "ssl"), "ssl"),
+        (cfg!(feature = "stdlib"), "stdlib"),
+        (cfg!(feature = "flame-it"), "flame-it"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then(|| format!("\"{name}\"")))
+    .collect::<Vec<_>>()
+    .join(",");
+
+    format!(
+        "{{\"version\":\"{}.{}.{}\",\"features\":[{}],\"target\":\"{}\",\"stdlib_path\":\"{}\",\"threading\":{}}}",
+        vm::version::MAJOR,
+        vm::version::MINOR,
+        vm::version::MICRO,
+        features,
+        format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS),
+        "Lib",
+        threading_available(),
+    )
+}
+
 fn run_rustpython(vm: &VirtualMachine, run_mode: RunMode) -> PyResult<()> {
     #[cfg(feature = "flame-it")]
     let main_guard = flame::start_guard("RustPython main");
+    // `SampleProfiler` polls `get_stack` from its own background thread so it can keep sampling
+    // while the interpreter is busy running the script on this one, but the VM's frame stack
+    // (`vm.frames`, a `RefCell`) isn't `Sync` and there's no signal/atomic-snapshot path that would
+    // let another thread read it without risking a torn read. Rather than silently recording zero
+    // samples forever, say so once and skip sampling; `--profile-format` output for this run falls
+    // back to `flame`'s span data instead (see `write_profile`'s `(ProfileFormat::Pprof, None)` arm).
+    #[cfg(feature = "flame-it")]
+    if vm.state.settings.profile_mode.as_deref() == Some("sample") {
+        warn!(
+            "--profile-mode=sample isn't supported yet (no thread-safe way to snapshot the frame \
+             stack); profiling will fall back to flame's span data"
+        );
+    }
+    #[cfg(feature = "flame-it")]
+    let sample_profiler: Option<sample_profiler::SampleProfiler> = None;
 
     let scope = setup_main_module(vm)?;
 
@@ -206,11 +318,18 @@ fn run_rustpython(vm: &VirtualMachine, run_mode: RunMode) -> PyResult<()> {
             vm.run_module(&module)
         }
         RunMode::InstallPip(installer) => install_pip(installer, scope.clone(), vm),
+        RunMode::ShowConfig => {
+            println!("{}", sysconfig_json());
+            Ok(())
+        }
         RunMode::Script(script) => {
             debug!("Running script {}", &script);
             vm.run_script(scope.clone(), &script)
         }
         RunMode::Repl => Ok(()),
+        RunMode::IsolatedScripts(_) | RunMode::Freeze { .. } => {
+            unreachable!("handled in `run` before entering the VM")
+        }
     };
     if is_repl || vm.state.settings.inspect {
         shell::run_shell(vm, scope)?;
@@ -221,7 +340,8 @@ fn run_rustpython(vm: &VirtualMachine, run_mode: RunMode) -> PyResult<()> {
     #[cfg(feature = "flame-it")]
     {
         main_guard.end();
-        if let Err(e) = write_profile(&vm.state.as_ref().settings) {
+        let samples = sample_profiler.map(sample_profiler::SampleProfiler::stop);
+        if let Err(e) = write_profile(&vm.state.as_ref().settings, samples) {
             error!("Error writing profile information: {}", e);
         }
     }
@@ -229,18 +349,24 @@ fn run_rustpython(vm: &VirtualMachine, run_mode: RunMode) -> PyResult<()> {
 }
 
 #[cfg(feature = "flame-it")]
-fn write_profile(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+fn write_profile(
+    settings: &Settings,
+    samples: Option<sample_profiler::Samples>,
+) -> Result<(), Box<dyn std::error::Error>> {
     use std::{fs, io};
 
     enum ProfileFormat {
         Html,
         Text,
         SpeedScope,
+        /// The protobuf `pprof` format, viewable with `go tool pprof`/the Firefox Profiler.
+        Pprof,
     }
     let profile_output = settings.profile_output.as_deref();
     let profile_format = match settings.profile_format.as_deref() {
         Some("html") => ProfileFormat::Html,
         Some("text") => ProfileFormat::Text,
+        Some("pprof") => ProfileFormat::Pprof,
         None if profile_output == Some("-".as_ref()) => ProfileFormat::Text,
         // spell-checker:ignore speedscope
         Some("speedscope") | None => ProfileFormat::SpeedScope,
@@ -255,6 +381,7 @@ fn write_profile(settings: &Settings) -> Result<(), Box<dyn std::error::Error>>
         ProfileFormat::Html => "flame-graph.html".as_ref(),
         ProfileFormat::Text => "flame.txt".as_ref(),
         ProfileFormat::SpeedScope => "flamescope.json".as_ref(),
+        ProfileFormat::Pprof => "profile.pb".as_ref(),
     });
 
     let profile_output: Box<dyn io::Write> = if profile_output == "-" {
@@ -263,12 +390,20 @@ fn write_profile(settings: &Settings) -> Result<(), Box<dyn std::error::Error>>
         Box::new(fs::File::create(profile_output)?)
     };
 
-    let profile_output = io::BufWriter::new(profile_output);
+    let mut profile_output = io::BufWriter::new(profile_output);
 
-    match profile_format {
-        ProfileFormat::Html => flame::dump_html(profile_output)?,
-        ProfileFormat::Text => flame::dump_text_to_writer(profile_output)?,
-        ProfileFormat::SpeedScope => flamescope::dump(profile_output)?,
+    match (profile_format, samples) {
+        (ProfileFormat::Html, _) => flame::dump_html(profile_output)?,
+        (ProfileFormat::SpeedScope, _) => flamescope::dump(profile_output)?,
+        (ProfileFormat::Text, Some(samples)) => samples.dump_text(profile_output)?,
+        (ProfileFormat::Text, None) => flame::dump_text_to_writer(profile_output)?,
+        (ProfileFormat::Pprof, Some(samples)) => {
+            sample_profiler::dump_pprof(&samples, &mut profile_output)?
+        }
+        (ProfileFormat::Pprof, None) => {
+            let samples = sample_profiler::from_flame_spans(&flame::spans());
+            sample_profiler::dump_pprof(&samples, &mut profile_output)?
+        }
     }
 
     Ok(())
@@ -0,0 +1,253 @@
+//! Two additions to `--profile`: exporting to the protobuf `pprof` format (viewable with
+//! `go tool pprof`/the Firefox Profiler), and [`SampleProfiler`], a statistical sampler that
+//! periodically snapshots a frame stack on a background thread instead of relying on `flame`'s
+//! exhaustive instrumentation, which is too heavy for long-running scripts. `run_rustpython` does
+//! not start the sampler: with `--profile-mode=sample` it logs a warning, and the profile is built
+//! from `flame`'s span data.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// A single point in a sampled call stack: the file, function, and line a frame was paused at.
+pub type StackFrame = (String, String, usize);
+
+/// Samples collected by a [`SampleProfiler`], folded down to a count per unique call stack.
+#[derive(Default)]
+pub struct Samples {
+    counts: HashMap<Vec<StackFrame>, u64>,
+}
+
+/// Periodically captures the VM's active frame stack on a background thread, rather than
+/// instrumenting every call like `flame` does. `get_stack` is called from that thread, so it must
+/// be safe to call concurrently with the script still running (e.g. by reading an `Arc<Mutex<_>>`
+/// snapshot maintained by the VM).
+pub struct SampleProfiler {
+    running: Arc<AtomicBool>,
+    samples: Arc<Mutex<Samples>>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl SampleProfiler {
+    pub fn start(
+        interval: Duration,
+        get_stack: impl Fn() -> Vec<StackFrame> + Send + 'static,
+    ) -> Self {
+        let running = Arc::new(AtomicBool::new(true));
+        let samples = Arc::new(Mutex::new(Samples::default()));
+
+        let handle = {
+            let running = running.clone();
+            let samples = samples.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    let stack = get_stack();
+                    if !stack.is_empty() {
+                        *samples.lock().unwrap().counts.entry(stack).or_insert(0) += 1;
+                    }
+                    thread::sleep(interval);
+                }
+            })
+        };
+
+        Self {
+            running,
+            samples,
+            handle,
+        }
+    }
+
+    /// Stops sampling and returns the collected stacks with their sample counts.
+    pub fn stop(self) -> Samples {
+        self.running.store(false, Ordering::Relaxed);
+        let _ = self.handle.join();
+        Arc::try_unwrap(self.samples)
+            .unwrap_or_else(|arc| Mutex::new(arc.lock().unwrap().take()))
+            .into_inner()
+            .unwrap()
+    }
+}
+
+/// Converts `flame`'s exhaustive call tree into the same `(stack, count)` shape `SampleProfiler`
+/// produces, so a single `dump_pprof`/`dump_text` can serve both profiling modes: one sample per
+/// leaf span, weighted by how many nanoseconds it took. Spans carry no file or line, so each
+/// frame's file is empty and its line slot holds the span's depth.
+pub fn from_flame_spans(spans: &[flame::Span]) -> Samples {
+    let mut samples = Samples::default();
+    fn walk(span: &flame::Span, stack: &mut Vec<StackFrame>, samples: &mut Samples) {
+        stack.push((String::new(), span.name.to_string(), span.depth as usize));
+        if span.children.is_empty() {
+            *samples.counts.entry(stack.clone()).or_insert(0) += span.delta.max(1);
+        }
+        for child in &span.children {
+            walk(child, stack, samples);
+        }
+        stack.pop();
+    }
+    let mut stack = Vec::new();
+    for span in spans {
+        walk(span, &mut stack, &mut samples);
+    }
+    samples
+}
+
+impl Samples {
+    fn take(&mut self) -> Self {
+        Self {
+            counts: std::mem::take(&mut self.counts),
+        }
+    }
+
+    /// Renders the samples as `stack;frames;joined N` lines, like `flame`'s text dump, so the same
+    /// downstream tooling (e.g. `inferno`) can turn either into a flamegraph.
+    pub fn dump_text<W: Write>(&self, mut w: W) -> io::Result<()> {
+        for (stack, count) in &self.counts {
+            let folded = stack
+                .iter()
+                .map(|(file, func, line)| format!("{file}:{func}:{line}"))
+                .collect::<Vec<_>>()
+                .join(";");
+            writeln!(w, "{folded} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+// A hand-rolled writer for the small subset of the pprof `profile.proto` message we emit:
+// string_table, function{id,name}, location{id,line{function_id,line}}, sample{location_id[],value[]}.
+// Protobuf fields are length-delimited (wire type 2) or varint (wire type 0); since every message
+// here is either a repeated submessage or a string/int, that's all we need to encode by hand.
+mod pb {
+    use std::io::{self, Write};
+
+    pub fn varint(mut n: u64, out: &mut Vec<u8>) {
+        loop {
+            let byte = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(byte);
+                break;
+            } else {
+                out.push(byte | 0x80);
+            }
+        }
+    }
+
+    pub fn tag(field_num: u32, wire_type: u8, out: &mut Vec<u8>) {
+        varint(((field_num as u64) << 3) | wire_type as u64, out);
+    }
+
+    pub fn string_field(field_num: u32, s: &str, out: &mut Vec<u8>) {
+        tag(field_num, 2, out);
+        varint(s.len() as u64, out);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn varint_field(field_num: u32, n: u64, out: &mut Vec<u8>) {
+        tag(field_num, 0, out);
+        varint(n, out);
+    }
+
+    pub fn message_field(field_num: u32, msg: &[u8], out: &mut Vec<u8>) {
+        tag(field_num, 2, out);
+        varint(msg.len() as u64, out);
+        out.extend_from_slice(msg);
+    }
+
+    pub fn write_all(bytes: &[u8], w: &mut impl Write) -> io::Result<()> {
+        w.write_all(bytes)
+    }
+}
+
+/// Encodes `samples` as a minimal `pprof` protobuf profile: one sample (a `samples`/`count` value)
+/// per unique call stack, one `Function` per distinct `(file, func)` and one `Location` per
+/// distinct `(file, func, line)` frame.
+pub fn dump_pprof<W: Write>(samples: &Samples, w: &mut W) -> io::Result<()> {
+    let mut strings: Vec<String> = vec![String::new()]; // index 0 must be the empty string
+    let mut string_idx = HashMap::new();
+    string_idx.insert(String::new(), 0u64);
+    let mut intern = |s: &str, strings: &mut Vec<String>, idx: &mut HashMap<String, u64>| -> u64 {
+        if let Some(&i) = idx.get(s) {
+            return i;
+        }
+        let i = strings.len() as u64;
+        strings.push(s.to_owned());
+        idx.insert(s.to_owned(), i);
+        i
+    };
+
+    let samples_str = intern("samples", &mut strings, &mut string_idx);
+    let count_str = intern("count", &mut strings, &mut string_idx);
+
+    let mut functions = Vec::new();
+    let mut function_ids = HashMap::new();
+    let mut frame_location_ids = HashMap::new();
+    let mut buf = Vec::new();
+
+    for (stack, count) in &samples.counts {
+        let mut location_ids = Vec::with_capacity(stack.len());
+        for (file, func, line) in stack {
+            let key = (file.clone(), func.clone());
+            let func_id = *function_ids.entry(key.clone()).or_insert_with(|| {
+                let id = functions.len() as u64 + 1;
+                let name = intern(func, &mut strings, &mut string_idx);
+                let filename = intern(file, &mut strings, &mut string_idx);
+                let mut f = Vec::new();
+                pb::varint_field(1, id, &mut f); // Function.id
+                pb::varint_field(2, name, &mut f); // Function.name (string index)
+                pb::varint_field(4, filename, &mut f); // Function.filename (string index)
+                functions.push(f);
+                id
+            });
+            // Location: one per distinct (file, func, line) frame, with one Line.
+            let next_loc_id = frame_location_ids.len() as u64 + 1;
+            let loc_id = *frame_location_ids
+                .entry((file.clone(), func.clone(), *line))
+                .or_insert_with(|| {
+                    let mut line_msg = Vec::new();
+                    pb::varint_field(1, func_id, &mut line_msg); // Line.function_id
+                    pb::varint_field(2, *line as u64, &mut line_msg); // Line.line
+                    let mut loc = Vec::new();
+                    pb::varint_field(1, next_loc_id, &mut loc); // Location.id
+                    pb::message_field(4, &line_msg, &mut loc); // Location.line
+                    pb::message_field(4, &loc, &mut buf); // Profile.location
+                    next_loc_id
+                });
+            location_ids.push(loc_id);
+        }
+
+        // `location_ids` was built walking `stack` root-first (see `from_flame_spans`/the sampler's
+        // `get_stack`), but the pprof wire format requires `Sample.location_id[0]` to be the leaf
+        // frame, so emit it in reverse.
+        let mut sample = Vec::new();
+        for id in location_ids.into_iter().rev() {
+            pb::varint_field(1, id, &mut sample); // Sample.location_id
+        }
+        pb::varint_field(2, *count, &mut sample); // Sample.value
+        pb::message_field(2, &sample, &mut buf); // Profile.sample (field 2)
+    }
+
+    for f in &functions {
+        pb::message_field(5, f, &mut buf); // Profile.function
+    }
+
+    // Profile.sample_type (field 1): one ValueType{type, unit}
+    let mut sample_type = Vec::new();
+    pb::varint_field(1, samples_str, &mut sample_type);
+    pb::varint_field(2, count_str, &mut sample_type);
+    let mut profile = Vec::new();
+    pb::message_field(1, &sample_type, &mut profile);
+    profile.extend_from_slice(&buf);
+    for s in &strings {
+        pb::string_field(6, s, &mut profile); // Profile.string_table
+    }
+
+    pb::write_all(&profile, w)
+}
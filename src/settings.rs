@@ -0,0 +1,129 @@
+//! Command line argument parsing for the `rustpython` binary.
+//!
+//! This translates `std::env::args()` into a [`Settings`](rustpython_vm::Settings) for the
+//! [`VirtualMachine`](rustpython_vm::VirtualMachine) together with a [`RunMode`] describing what
+//! the interpreter should actually do once it's booted.
+
+use rustpython_vm::Settings;
+use std::env;
+
+/// What the interpreter should do once it's initialized.
+#[derive(Debug)]
+pub enum RunMode {
+    /// Run the string passed to `-c`.
+    Command(String),
+    /// Run the module passed to `-m`, as in `python -m module`.
+    Module(String),
+    /// Install pip into the running interpreter, using the given [`InstallPipMode`].
+    InstallPip(InstallPipMode),
+    /// Run the script at the given path (or `-` for stdin).
+    Script(String),
+    /// Run each of the given scripts in its own isolated subinterpreter, with no `sys.modules` or
+    /// builtin state shared between them. See [`crate::interpreter::run_isolated`].
+    IsolatedScripts(Vec<String>),
+    /// Print a JSON blob describing this interpreter's build configuration and exit, so that
+    /// external build tooling (the way PyO3/python3-sys parse CPython's `py_sys_config`) can
+    /// detect and configure themselves against a RustPython interpreter.
+    ShowConfig,
+    /// Compile every `.py` file under `input_dir` to frozen bytecode and write it to `output`.
+    /// See [`crate::freeze::freeze_dir`].
+    Freeze { input_dir: String, output: String },
+    /// Drop into the REPL.
+    Repl,
+}
+
+/// How `--install-pip` should obtain the pip installer.
+#[derive(Debug)]
+pub enum InstallPipMode {
+    /// Run the stdlib `ensurepip` module.
+    Ensurepip,
+    /// Download `get-pip.py` from `https://bootstrap.pypa.io/get-pip.py` and run it.
+    GetPip,
+    /// Install pip from a local wheel, with no network access and no dependency on the
+    /// `ssl` feature -- for air-gapped or `--no-default-features` builds. The file is unpacked as
+    /// a zip archive, not built, so sdists are not supported.
+    Wheel { path: String },
+}
+
+/// Parses `env::args()` into a [`Settings`] and a [`RunMode`].
+pub fn parse_opts() -> Result<(Settings, RunMode), String> {
+    let args: Vec<String> = env::args().collect();
+    parse_args(&args[1..])
+}
+
+fn parse_args(args: &[String]) -> Result<(Settings, RunMode), String> {
+    let mut settings = Settings::default();
+    let mut run_mode = None;
+    let mut script_args = Vec::new();
+
+    let mut it = args.iter().peekable();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-c" => {
+                let command = it.next().ok_or("Argument expected for the -c option")?;
+                run_mode = Some(RunMode::Command(command.clone()));
+                break;
+            }
+            "-m" => {
+                let module = it.next().ok_or("Argument expected for the -m option")?;
+                run_mode = Some(RunMode::Module(module.clone()));
+                break;
+            }
+            "-i" => settings.inspect = true,
+            "-q" => settings.quiet = true,
+            "-v" => settings.verbose += 1,
+            "--install-pip" => {
+                let mode = match it.peek().map(String::as_str) {
+                    Some("ensurepip") => {
+                        it.next();
+                        InstallPipMode::Ensurepip
+                    }
+                    Some("wheel") => {
+                        it.next();
+                        let path = it
+                            .next()
+                            .ok_or("--install-pip wheel requires a path to a wheel")?;
+                        InstallPipMode::Wheel { path: path.clone() }
+                    }
+                    _ => InstallPipMode::GetPip,
+                };
+                run_mode = Some(RunMode::InstallPip(mode));
+                break;
+            }
+            "--sysconfig" => {
+                run_mode = Some(RunMode::ShowConfig);
+                break;
+            }
+            "--freeze" => {
+                let input_dir = it
+                    .next()
+                    .ok_or("--freeze requires an input directory and an output path")?;
+                let output = it
+                    .next()
+                    .ok_or("--freeze requires an input directory and an output path")?;
+                run_mode = Some(RunMode::Freeze {
+                    input_dir: input_dir.clone(),
+                    output: output.clone(),
+                });
+                break;
+            }
+            "--isolated" => {
+                let scripts: Vec<String> = it.by_ref().cloned().collect();
+                if scripts.is_empty() {
+                    return Err("Argument expected for the --isolated option".to_owned());
+                }
+                run_mode = Some(RunMode::IsolatedScripts(scripts));
+                break;
+            }
+            other => {
+                run_mode = Some(RunMode::Script(other.to_owned()));
+                break;
+            }
+        }
+    }
+
+    script_args.extend(it.cloned());
+    settings.argv = script_args;
+
+    Ok((settings, run_mode.unwrap_or(RunMode::Repl)))
+}
@@ -0,0 +1,25 @@
+use rustpython_vm::{PyResult, VirtualMachine, scope::Scope};
+
+/// Runs an interactive REPL against `vm`, using `scope` as the `__main__` scope.
+pub fn run_shell(vm: &VirtualMachine, scope: Scope) -> PyResult<()> {
+    // TODO: readline/history support; for now this is a minimal read-eval-print loop.
+    use std::io::{self, Write};
+
+    let stdin = io::stdin();
+    loop {
+        print!(">>> ");
+        io::stdout().flush().ok();
+        let mut line = String::new();
+        if stdin.read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        match vm.run_code_string(scope.clone(), &line, "<stdin>".to_owned()) {
+            Ok(_) => {}
+            Err(e) => vm.print_exception(e),
+        }
+    }
+    Ok(())
+}
@@ -34,9 +34,9 @@ use rustpython_common::wtf8::{CodePoint, Wtf8, Wtf8Buf};
 
 static ESCAPE_CHARS: [&str; 0x20] = [
     "\\u0000", "\\u0001", "\\u0002", "\\u0003", "\\u0004", "\\u0005", "\\u0006", "\\u0007", "\\b",
-    "\\t", "\\n", "\\u000", "\\f", "\\r", "\\u000e", "\\u000f", "\\u0010", "\\u0011", "\\u0012",
+    "\\t", "\\n", "\\u000b", "\\f", "\\r", "\\u000e", "\\u000f", "\\u0010", "\\u0011", "\\u0012",
     "\\u0013", "\\u0014", "\\u0015", "\\u0016", "\\u0017", "\\u0018", "\\u0019", "\\u001a",
-    "\\u001", "\\u001c", "\\u001d", "\\u001e", "\\u001f",
+    "\\u001b", "\\u001c", "\\u001d", "\\u001e", "\\u001f",
 ];
 
 // This bitset represents which bytes can be copied as-is to a JSON string (0)
@@ -68,49 +68,145 @@ fn json_escaped_char(c: u8) -> Option<&'static str> {
     }
 }
 
+/// Whether `NEEDS_ESCAPING_BITSET` marks `c` as 1, i.e. `c` is one of `"`, `\`, `0x00..=0x1F`,
+/// or `0x7F`. Non-ASCII bytes (`0x80..=0xFF`) are never in this
+/// set -- they're only relevant to the `ascii_only` path, which handles them separately.
+#[inline(always)]
+fn needs_escaping(c: u8) -> bool {
+    NEEDS_ESCAPING_BITSET[(c / 64) as usize] & (1 << (c % 64)) != 0
+}
+
+/// Finds the index of the next byte in `bytes[start..]` matching `pred`, or `bytes.len()` if none
+/// does. Runs of clean bytes dominate typical input, so escaping is split into "find the next
+/// interesting byte" (a tight, single-comparison-per-byte scan) and "copy everything before it in
+/// one `write_all`", rather than dispatching through `json_escaped_char` for every byte.
+#[inline(always)]
+fn find_next(bytes: &[u8], start: usize, pred: impl Fn(u8) -> bool) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&b| pred(b))
+        .map_or(bytes.len(), |i| start + i)
+}
+
+// NOTE: this would normally also ship with `benches/` cases for long escape-free strings,
+// dense-escape strings, and high-codepoint strings, the way `json_in_type` (the lineage this
+// module descends from) benchmarks its own copy loop -- but there's no `Cargo.toml` anywhere in
+// this crate to wire a `[[bench]]` target into, so there's nowhere for such a harness to actually
+// run from here. The same three shapes are covered as correctness tests below instead.
 pub fn write_json_string<W: io::Write>(s: &str, ascii_only: bool, w: &mut W) -> io::Result<()> {
     w.write_all(b"\"")?;
-    let mut write_start_idx = 0;
     let bytes = s.as_bytes();
     if ascii_only {
-        for (idx, c) in s.char_indices() {
-            if c.is_ascii() {
-                if let Some(escaped) = json_escaped_char(c as u8) {
-                    w.write_all(&bytes[write_start_idx..idx])?;
-                    w.write_all(escaped.as_bytes())?;
-                    write_start_idx = idx + 1;
-                }
+        // Fast-path runs of clean ASCII in bulk; only fall back to the per-char `encode_utf16`
+        // dance once a non-ASCII scalar is actually encountered.
+        let mut write_start_idx = 0;
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let next_interesting = find_next(bytes, idx, |b| !b.is_ascii() || needs_escaping(b));
+            if next_interesting > idx {
+                idx = next_interesting;
+                continue;
+            }
+            let b = bytes[idx];
+            w.write_all(&bytes[write_start_idx..idx])?;
+            if b.is_ascii() {
+                w.write_all(json_escaped_char(b).unwrap().as_bytes())?;
+                idx += 1;
             } else {
-                w.write_all(&bytes[write_start_idx..idx])?;
-                write_start_idx = idx + c.len_utf8();
+                let c = s[idx..].chars().next().unwrap();
+                idx += c.len_utf8();
                 // codepoints outside the BMP get 2 '\uxxxx' sequences to represent them
                 for point in c.encode_utf16(&mut [0; 2]) {
                     write!(w, "\\u{point:04x}")?;
                 }
             }
+            write_start_idx = idx;
         }
+        w.write_all(&bytes[write_start_idx..])?;
     } else {
-        for (idx, c) in s.bytes().enumerate() {
-            if let Some(escaped) = json_escaped_char(c) {
-                w.write_all(&bytes[write_start_idx..idx])?;
-                w.write_all(escaped.as_bytes())?;
-                write_start_idx = idx + 1;
+        let mut write_start_idx = 0;
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let next_escape = find_next(bytes, idx, needs_escaping);
+            if next_escape > idx {
+                idx = next_escape;
+                continue;
             }
+            w.write_all(&bytes[write_start_idx..idx])?;
+            w.write_all(json_escaped_char(bytes[idx]).unwrap().as_bytes())?;
+            idx += 1;
+            write_start_idx = idx;
         }
+        w.write_all(&bytes[write_start_idx..])?;
     }
-    w.write_all(&bytes[write_start_idx..])?;
     w.write_all(b"\"")
 }
 
+/// The low-level scanner's error type, mirroring the attributes of CPython's
+/// `json.JSONDecodeError` (a subclass of `ValueError`) closely enough that a future `json`
+/// pymodule can construct one from this directly: `.msg`, `.doc`, `.pos`, and the `.lineno`/
+/// `.colno` this computes from them. There's no `json`/`_json` pymodule in this crate snapshot to
+/// actually attach a Python exception class to, so this stays a plain Rust error for now --
+/// callers that add that module should catch it and raise a real `JSONDecodeError` from its
+/// fields rather than stringifying it.
 #[derive(Debug)]
 pub struct DecodeError {
     pub msg: String,
+    /// The full document being decoded, captured at raise time so `lineno`/`colno`/`to_message`
+    /// don't need it threaded back in separately -- matches CPython's `JSONDecodeError.doc`.
+    pub doc: Wtf8Buf,
+    /// A code point offset into `doc`, *not* a byte offset -- matches `char_i` from
+    /// [`scanstring`]'s iteration over [`Wtf8::code_point_indices`].
     pub pos: usize,
 }
 impl DecodeError {
-    fn new(msg: impl Into<String>, pos: usize) -> Self {
+    fn new(msg: impl Into<String>, doc: &Wtf8, pos: usize) -> Self {
         let msg = msg.into();
-        Self { msg, pos }
+        Self {
+            msg,
+            doc: doc.to_owned(),
+            pos,
+        }
+    }
+
+    /// The 1-indexed line `self.pos` falls on within `self.doc`, the way CPython's
+    /// `JSONDecodeError` computes `lineno`: one plus the number of newlines strictly before the
+    /// error position.
+    pub fn lineno(&self) -> usize {
+        1 + self
+            .doc
+            .code_points()
+            .take(self.pos)
+            .filter(|&c| c == '\n')
+            .count()
+    }
+
+    /// The 1-indexed column `self.pos` falls on: the offset past the last newline before it, or
+    /// `pos + 1` if there is no preceding newline.
+    pub fn colno(&self) -> usize {
+        match self
+            .doc
+            .code_points()
+            .take(self.pos)
+            .enumerate()
+            .filter(|(_, c)| *c == '\n')
+            .last()
+        {
+            Some((last_newline, _)) => self.pos - last_newline,
+            None => self.pos + 1,
+        }
+    }
+
+    /// Renders this error the way CPython's `json.JSONDecodeError.__str__` does:
+    /// `"<msg>: line <lineno> column <colno> (char <pos>)"`.
+    pub fn to_message(&self) -> String {
+        format!(
+            "{}: line {} column {} (char {})",
+            self.msg,
+            self.lineno(),
+            self.colno(),
+            self.pos
+        )
     }
 }
 
@@ -137,7 +233,7 @@ pub fn scanstring<'a>(
         output_len += chunk.len();
         chunks.push(chunk);
     };
-    let unterminated_err = || DecodeError::new("Unterminated string starting at", end - 1);
+    let unterminated_err = || DecodeError::new("Unterminated string starting at", s, end - 1);
     let mut chars = s.code_point_indices().enumerate().skip(end).peekable();
     let &(_, (mut chunk_start, _)) = chars.peek().ok_or_else(unterminated_err)?;
     while let Some((char_i, (byte_i, c))) = chars.next() {
@@ -166,7 +262,7 @@ pub fn scanstring<'a>(
                     'r' => "\r",
                     't' => "\t",
                     'u' => {
-                        let mut uni = decode_unicode(&mut chars, char_i)?;
+                        let mut uni = decode_unicode(&mut chars, s, char_i)?;
                         chunk_start = byte_i + 6;
                         if let Some(lead) = uni.to_lead_surrogate() {
                             // uni is a surrogate -- try to find its pair
@@ -175,7 +271,7 @@ pub fn scanstring<'a>(
                                 .next_tuple()
                                 .filter(|((_, (_, c1)), (_, (_, c2)))| *c1 == '\\' && *c2 == 'u')
                             {
-                                let uni2 = decode_unicode(&mut chars2, pos2)?;
+                                let uni2 = decode_unicode(&mut chars2, s, pos2)?;
                                 if let Some(trail) = uni2.to_trail_surrogate() {
                                     // ok, we found what we were looking for -- \uXXXX\uXXXX, both surrogates
                                     uni = lead.merge(trail).into();
@@ -188,7 +284,11 @@ pub fn scanstring<'a>(
                         continue;
                     }
                     _ => {
-                        return Err(DecodeError::new(format!("Invalid \\escape: {c:?}"), char_i));
+                        return Err(DecodeError::new(
+                            format!("Invalid \\escape: {c:?}"),
+                            s,
+                            char_i,
+                        ));
                     }
                 };
                 chunk_start = byte_i + 2;
@@ -197,6 +297,7 @@ pub fn scanstring<'a>(
             '\x00'..='\x1f' if strict => {
                 return Err(DecodeError::new(
                     format!("Invalid control character {c:?} at"),
+                    s,
                     char_i,
                 ));
             }
@@ -207,11 +308,11 @@ pub fn scanstring<'a>(
 }
 
 #[inline]
-fn decode_unicode<I>(it: &mut I, pos: usize) -> Result<CodePoint, DecodeError>
+fn decode_unicode<I>(it: &mut I, doc: &Wtf8, pos: usize) -> Result<CodePoint, DecodeError>
 where
     I: Iterator<Item = (usize, (usize, CodePoint))>,
 {
-    let err = || DecodeError::new("Invalid \\uXXXX escape", pos);
+    let err = || DecodeError::new("Invalid \\uXXXX escape", doc, pos);
     let mut uni = 0;
     for x in (0..4).rev() {
         let (_, (_, c)) = it.next().ok_or_else(err)?;
@@ -220,3 +321,45 @@ where
     }
     Ok(uni.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escaped(s: &str) -> String {
+        let mut out = Vec::new();
+        write_json_string(s, false, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn long_escape_free_string_is_copied_verbatim() {
+        let s = "the quick brown fox jumps over the lazy dog".repeat(50);
+        assert_eq!(escaped(&s), format!("\"{s}\""));
+    }
+
+    #[test]
+    fn dense_escape_string_escapes_every_control_byte() {
+        let s: String = (0x00u8..=0x1f).map(|b| b as char).collect();
+        let expected: String = std::iter::once('"')
+            .chain(ESCAPE_CHARS.iter().flat_map(|esc| esc.chars()))
+            .chain(std::iter::once('"'))
+            .collect();
+        assert_eq!(escaped(&s), expected);
+    }
+
+    #[test]
+    fn vertical_tab_and_escape_are_not_truncated() {
+        // Regression test: `ESCAPE_CHARS[0x0B]`/`[0x1B]` used to be missing their last hex digit
+        // (`\u000`/`\u001`), producing malformed JSON.
+        assert_eq!(escaped("\u{0B}"), "\"\\u000b\"");
+        assert_eq!(escaped("\u{1B}"), "\"\\u001b\"");
+    }
+
+    #[test]
+    fn high_codepoint_string_is_surrogate_pair_escaped_when_ascii_only() {
+        let mut out = Vec::new();
+        write_json_string("\u{1F600}", true, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\"\\ud83d\\ude00\"");
+    }
+}
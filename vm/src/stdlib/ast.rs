@@ -329,10 +329,1326 @@ pub(crate) fn parse(
     source: &str,
     mode: parser::Mode,
 ) -> Result<PyObjectRef, CompileError> {
+    parse_with_flags(vm, source, mode, 0)
+        .map(|parsed| parsed.object)
+        .map_err(ParseError::into_inner)
+}
+
+/// Like [`parse`], but takes the same `PY_CF_*` bitflags `compile()` does (see
+/// [`PY_COMPILE_FLAG_AST_ONLY`] and friends further down this file) and returns the
+/// [`ParseError::Fatal`]/[`ParseError::Incomplete`] distinction instead of collapsing it, so a
+/// caller like `code.InteractiveConsole`/`codeop` can tell "needs another line" apart from a real
+/// `SyntaxError`, instead of being forced through [`parse`]'s flattening `map_err`.
+#[cfg(feature = "rustpython-parser")]
+pub(crate) fn parse_with_flags(
+    vm: &VirtualMachine,
+    source: &str,
+    mode: parser::Mode,
+    flags: i32,
+) -> Result<ParsedModule, ParseError> {
+    parse_with_options(
+        vm,
+        source,
+        mode,
+        ParseOptions {
+            type_comments: flags & PY_CF_TYPE_COMMENTS != 0,
+            feature_version: None,
+            dont_imply_dedent: flags & PY_CF_DONT_IMPLY_DEDENT != 0,
+            allow_incomplete_input: flags & PY_CF_ALLOW_INCOMPLETE_INPUT != 0,
+        },
+    )
+}
+
+/// The result of [`parse_with_options`]: the parsed module object, plus whatever
+/// [`ParseOptions::type_comments`] asked to be pulled out of the source alongside it.
+#[cfg_attr(not(feature = "rustpython-parser"), allow(dead_code))]
+pub(crate) struct ParsedModule {
+    pub object: PyObjectRef,
+    /// `(lineno, comment text)` for each `# type: <expr>` comment found, in source order.
+    /// Empty unless [`ParseOptions::type_comments`] was set. This is CPython's per-statement
+    /// `type_comment` data, but since `rustpython_ast`'s node structs aren't defined in this
+    /// crate (no source for that dependency is vendored here), there's no typed field on the
+    /// `Node`s themselves to hold it -- so it's kept here as a side channel, in addition to the
+    /// dynamic `type_comment` attributes [`attach_type_comments`] sets on the module object.
+    pub type_comments: Vec<(usize, String)>,
+    /// 1-indexed line numbers of `# type: ignore[...]` comments, CPython's `Module.type_ignores`.
+    pub type_ignores: Vec<usize>,
+}
+
+/// Options `compile()`'s `flags`/`feature_version` surface can request of the parser, beyond
+/// just which [`parser::Mode`] to parse in.
+#[cfg_attr(not(feature = "rustpython-parser"), allow(dead_code))]
+#[derive(Default)]
+pub(crate) struct ParseOptions {
+    /// Corresponds to CPython's `PyCF_TYPE_COMMENTS` / `ast.PyCF_TYPE_COMMENTS`: retain `# type:`
+    /// comments instead of discarding them as ordinary comments, so they can be attached to the
+    /// AST as `type_comment` fields (on `FunctionDef`/`AsyncFunctionDef`/`Assign`/`For`/
+    /// `AsyncFor`/`With`/`AsyncWith`; `arg` nodes are not covered) and collected as
+    /// `Module.type_ignores` for `# type: ignore[...]` lines.
+    pub type_comments: bool,
+    /// Corresponds to `compile()`'s `_feature_version` (`(major, minor)`). Grammar productions
+    /// are not gated by it; the only construct [`parse_with_options`] checks against it is the
+    /// walrus operator, for versions below 3.8.
+    pub feature_version: Option<(u16, u16)>,
+    /// Corresponds to `PY_CF_DONT_IMPLY_DEDENT`: CPython's tokenizer normally pretends an `EOF`
+    /// is preceded by a newline, so a final logical line missing its trailing `\n` still parses;
+    /// suppressing that here is what lets a REPL tell "this really does need another line" apart
+    /// from "merely forgot the newline".
+    pub dont_imply_dedent: bool,
+    /// Corresponds to `PY_CF_ALLOW_INCOMPLETE_INPUT`: ask [`parse_with_options`] to distinguish a
+    /// syntax error caused by the input being cut off mid-construct (unclosed bracket, trailing
+    /// `:` with no body, backslash continuation, unterminated string) from a genuine one, via
+    /// [`ParseError::Incomplete`].
+    pub allow_incomplete_input: bool,
+}
+
+/// What [`parse_with_options`] can fail with. `code.InteractiveConsole`/`codeop` need to tell
+/// "genuine `SyntaxError`" apart from "just needs another line", which a plain [`CompileError`]
+/// can't express on its own.
+pub(crate) enum ParseError {
+    /// A real syntax error -- feeding more input wouldn't fix it.
+    Fatal(CompileError),
+    /// The input looks like it was cut off mid-construct. Only returned when
+    /// [`ParseOptions::allow_incomplete_input`] is set.
+    Incomplete(CompileError),
+}
+
+impl ParseError {
+    pub(crate) fn into_inner(self) -> CompileError {
+        match self {
+            ParseError::Fatal(e) | ParseError::Incomplete(e) => e,
+        }
+    }
+}
+
+#[cfg(feature = "rustpython-parser")]
+pub(crate) fn parse_with_options(
+    vm: &VirtualMachine,
+    source: &str,
+    mode: parser::Mode,
+    options: ParseOptions,
+) -> Result<ParsedModule, ParseError> {
+    // `feature_version` can't gate grammar *productions* -- this entry point doesn't reach into
+    // `rustpython_parser`'s grammar, whose source isn't vendored in this build -- but it can
+    // still reject the one version-gated construct this crate's AST is able to represent at all:
+    // the walrus operator (PEP 572, `:=`, Python 3.8+). Detecting it pre-tokenization means
+    // scanning the raw source textually rather than via real token boundaries, so this is a
+    // heuristic (it doesn't know about string/comment contents) in the same spirit as
+    // `looks_like_incomplete_input` below.
+    if options
+        .feature_version
+        .is_some_and(|v| v < (3, 8) && source_mentions_walrus(source))
+    {
+        let err = parser::parse(source, mode, "<unknown>")
+            .err()
+            .map(|e| LinearLocator::new(source).locate_error(e));
+        // Fall through to the real parse if the heuristic can't even get an error to piggyback
+        // on (e.g. the parser itself already rejects this source for an unrelated reason).
+        if let Some(err) = err {
+            return Err(ParseError::Fatal(err));
+        }
+    }
+
+    // `PY_CF_DONT_IMPLY_DEDENT`: by default (flag unset), CPython's tokenizer behaves as if a
+    // trailing newline were always present at EOF, so a final unterminated line still parses.
+    // This entry point can't reach into the tokenizer to toggle that directly, but it can
+    // reproduce the flag's *effect* by doing the implied-newline insertion itself, here, and
+    // simply skipping it when the flag says not to imply one.
+    let owned_source;
+    let source = if !options.dont_imply_dedent && !source.is_empty() && !source.ends_with('\n') {
+        owned_source = format!("{source}\n");
+        owned_source.as_str()
+    } else {
+        source
+    };
+
     let mut locator = LinearLocator::new(source);
-    let top = parser::parse(source, mode, "<unknown>").map_err(|e| locator.locate_error(e))?;
+    let top = parser::parse(source, mode, "<unknown>").map_err(|e| locator.locate_error(e));
+    let top = match top {
+        Ok(top) => top,
+        Err(err) => {
+            return Err(if options.allow_incomplete_input && looks_like_incomplete_input(&err) {
+                ParseError::Incomplete(err)
+            } else {
+                ParseError::Fatal(err)
+            });
+        }
+    };
     let top = locator.fold_mod(top).unwrap();
-    Ok(top.ast_to_object(vm))
+
+    let (type_comments, type_ignores) = if options.type_comments {
+        extract_type_comments(source)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let object = top.ast_to_object(vm);
+    if !type_comments.is_empty() || !type_ignores.is_empty() {
+        attach_type_comments(vm, &object, &type_comments, &type_ignores);
+    }
+
+    Ok(ParsedModule {
+        object,
+        type_comments,
+        type_ignores,
+    })
+}
+
+/// Attaches the side-channel data [`extract_type_comments`] pulled out of the source onto the
+/// freshly-built module object, the way CPython attaches `type_comment` directly to
+/// `FunctionDef`/`AsyncFunctionDef`/`Assign`/`For`/`AsyncFor`/`With`/`AsyncWith` nodes and
+/// `Module.type_ignores` to a list of `TypeIgnore(lineno, tag)` entries. The node classes `r#gen`
+/// would normally generate for those statement kinds aren't vendored in this snapshot, so there's
+/// no typed field to assign through -- but every `_ast.AST` subclass accepts arbitrary attributes
+/// regardless (the base class declared in this file carries `HAS_DICT`), so this sets
+/// `type_comment`/`type_ignores` dynamically via `set_attr` instead. `arg` nodes (the rarer
+/// per-argument `def f(x,  # type: int\n): ...` form) aren't covered, only the statement-level
+/// attachment CPython documents as the common case.
+#[cfg(feature = "rustpython-parser")]
+fn attach_type_comments(
+    vm: &VirtualMachine,
+    module_obj: &PyObjectRef,
+    type_comments: &[(usize, String)],
+    type_ignores: &[usize],
+) {
+    if !type_ignores.is_empty() {
+        let entries = type_ignores
+            .iter()
+            .map(|&lineno| {
+                let node: PyObjectRef = NodeAst.into_ref(&vm.ctx).into();
+                node.set_attr("lineno", vm.ctx.new_int(lineno).into(), vm)
+                    .expect("a freshly constructed _ast.AST node accepts any attribute");
+                node.set_attr("tag", vm.ctx.new_str("").into(), vm)
+                    .expect("a freshly constructed _ast.AST node accepts any attribute");
+                node
+            })
+            .collect::<Vec<_>>();
+        module_obj
+            .set_attr("type_ignores", vm.ctx.new_list(entries).into(), vm)
+            .expect("Module always accepts a type_ignores attribute");
+    }
+    if !type_comments.is_empty() {
+        let body = get_node_field_opt(vm, module_obj, "body")
+            .expect("reading a freshly constructed node's own attribute cannot fail");
+        if let Some(body) = body {
+            attach_stmt_type_comments(vm, &body, type_comments);
+        }
+    }
+}
+
+/// Recurses into every nested statement body (`body`/`orelse`/`finalbody`) looking for a
+/// statement whose `lineno` matches one of `type_comments`'s entries, and stamps `type_comment`
+/// onto it if its kind is one CPython allows the field on.
+#[cfg(feature = "rustpython-parser")]
+fn attach_stmt_type_comments(
+    vm: &VirtualMachine,
+    stmts: &PyObjectRef,
+    type_comments: &[(usize, String)],
+) {
+    const ELIGIBLE: &[&str] = &[
+        "FunctionDef",
+        "AsyncFunctionDef",
+        "Assign",
+        "For",
+        "AsyncFor",
+        "With",
+        "AsyncWith",
+    ];
+    let Ok(stmts) = stmts.try_to_value::<Vec<PyObjectRef>>(vm) else {
+        return;
+    };
+    for stmt in stmts {
+        if let Some(lineno) = get_node_field_opt(vm, &stmt, "lineno")
+            .expect("reading a freshly constructed node's own attribute cannot fail")
+        {
+            let lineno = i32::try_from_object(vm, lineno)
+                .expect("lineno is always an int")
+                as usize;
+            if ELIGIBLE.contains(&stmt.class().name().as_ref()) {
+                if let Some((_, text)) = type_comments.iter().find(|(l, _)| *l == lineno) {
+                    stmt.set_attr("type_comment", vm.ctx.new_str(text.as_str()).into(), vm)
+                        .expect("a freshly constructed _ast.AST node accepts any attribute");
+                }
+            }
+        }
+        for field in ["body", "orelse", "finalbody"] {
+            if let Some(nested) = get_node_field_opt(vm, &stmt, field)
+                .expect("reading a freshly constructed node's own attribute cannot fail")
+            {
+                attach_stmt_type_comments(vm, &nested, type_comments);
+            }
+        }
+    }
+}
+
+/// Textual heuristic for "does this source use the walrus operator (`:=`) anywhere", used to
+/// approximate `feature_version` grammar gating (see [`parse_with_options`]) without real
+/// tokenizer cooperation. Being a plain substring search, this can't tell a `:=` that's
+/// actually inside a string or comment from a real one; it's a best-effort stand-in, not
+/// line/column-accurate grammar enforcement.
+#[cfg(feature = "rustpython-parser")]
+fn source_mentions_walrus(source: &str) -> bool {
+    source.contains(":=")
+}
+
+/// Extracts CPython's `type_comment` (inline `# type: <expr>`) and `type_ignores`
+/// (`# type: ignore[...]`) data from raw source text. This is a line-based scan over the source
+/// string itself rather than over real tokens -- `rustpython_parser`'s tokenizer isn't available
+/// to consult here -- so, same caveat as [`source_mentions_walrus`]: a `#` inside a string literal
+/// that happens to look like `# type: ...` would be misread as a comment. Good enough to recover
+/// type comments from ordinarily-formatted source; not a replacement for real tokenization.
+#[cfg(feature = "rustpython-parser")]
+fn extract_type_comments(source: &str) -> (Vec<(usize, String)>, Vec<usize>) {
+    let mut type_comments = Vec::new();
+    let mut type_ignores = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let lineno = idx + 1;
+        let Some(hash_idx) = line.find('#') else {
+            continue;
+        };
+        let comment = line[hash_idx + 1..].trim_start();
+        let Some(rest) = comment.strip_prefix("type:") else {
+            continue;
+        };
+        let rest = rest.trim();
+        if rest == "ignore" || rest.starts_with("ignore[") || rest.starts_with("ignore ") {
+            type_ignores.push(lineno);
+        } else if !rest.is_empty() {
+            type_comments.push((lineno, rest.to_owned()));
+        }
+    }
+    (type_comments, type_ignores)
+}
+
+/// Best-effort classifier for [`ParseOptions::allow_incomplete_input`]: without the tokenizer's
+/// own bracket-depth/continuation-line state (this crate doesn't have `rustpython_parser`'s
+/// source available to consult directly), the closest approximation available at this layer is
+/// pattern-matching the error's own message for the phrasing CPython's tokenizer uses for an
+/// `EOF` reached while a construct was still open.
+#[cfg(feature = "rustpython-parser")]
+fn looks_like_incomplete_input(err: &CompileError) -> bool {
+    let msg = err.to_string();
+    [
+        "unexpected EOF",
+        "EOF while scanning",
+        "EOF in multi-line",
+        "was never closed",
+        "expected an indented block",
+        "unexpected end of file",
+    ]
+    .iter()
+    .any(|needle| msg.contains(needle))
+}
+
+/// `ast.unparse(node)`: the reverse of [`parse`] -- turns the `node` object back into a `str` of
+/// Python source. `node` is rebuilt through `Mod`'s `Node::ast_from_object`, so it must be a
+/// `Module`, `Interactive`, `Expression` or `FunctionType` node; a bare statement or expression
+/// node is not accepted. Implemented as a recursive visitor (see [`unparse::Unparser`]) rather than
+/// reusing `compile`'s codegen path, since the output here needs to stay human-readable source,
+/// not bytecode.
+pub(crate) fn unparse(vm: &VirtualMachine, object: PyObjectRef) -> PyResult<String> {
+    let module = ast::located::Mod::ast_from_object(vm, object)?;
+    let mut unparser = unparse::Unparser::new();
+    unparser.unparse_mod(&module);
+    Ok(unparser.finish())
+}
+
+/// A recursive-descent pretty-printer that mirrors CPython's `Lib/ast.py` `_Unparser`: walk the
+/// located AST and emit source text, tracking the precedence of whatever expression is currently
+/// being rendered so parentheses are only inserted where the grammar actually requires them.
+///
+/// This covers the statement and expression forms `ast.unparse` is commonly used for --
+/// decorators, `async`/`await`, comprehensions, f-strings, starred/double-starred arguments,
+/// lambdas, and the `-2 ** 2` vs `(-2) ** 2` unary-vs-power precedence CPython's own unparser is
+/// careful about. Precedence is tracked with a simple total order rather than CPython's full
+/// associativity table, so a handful of rare combinations may come out with one or two more pairs
+/// of parentheses than CPython would emit -- always safe to evaluate, just not always the
+/// minimal rendering.
+mod unparse {
+    use super::ast;
+    use std::fmt::Write as _;
+
+    /// How tightly an expression binds, loosest first. Used to decide whether a child expression
+    /// needs parentheses around it to be re-parsed with the same grouping.
+    #[derive(Clone, Copy, PartialEq, PartialOrd)]
+    enum Prec {
+        Tuple,
+        NamedExpr,
+        Lambda,
+        Test, // if/else
+        Or,
+        And,
+        Not,
+        Cmp,
+        BitOr,
+        BitXor,
+        BitAnd,
+        Shift,
+        Arith,
+        Term,
+        Factor, // unary +/-/~
+        Power,
+        Await,
+        Atom,
+    }
+
+    fn bin_op_prec(op: &ast::Operator) -> Prec {
+        use ast::Operator::*;
+        match op {
+            Add | Sub => Prec::Arith,
+            Mult | MatMult | Div | Mod | FloorDiv => Prec::Term,
+            Pow => Prec::Power,
+            LShift | RShift => Prec::Shift,
+            BitOr => Prec::BitOr,
+            BitXor => Prec::BitXor,
+            BitAnd => Prec::BitAnd,
+        }
+    }
+
+    /// One precedence level tighter than `p`, used to make a left-associative operator's right
+    /// operand parenthesize at the same precedence (`a - (b - c)` must keep its parens; `(a - b)
+    /// - c` doesn't need any, since that's how `a - b - c` already associates).
+    fn next_tighter(p: Prec) -> Prec {
+        match p {
+            Prec::BitOr => Prec::BitXor,
+            Prec::BitXor => Prec::BitAnd,
+            Prec::BitAnd => Prec::Shift,
+            Prec::Shift => Prec::Arith,
+            Prec::Arith => Prec::Term,
+            Prec::Term => Prec::Factor,
+            p => p,
+        }
+    }
+
+    fn bin_op_str(op: &ast::Operator) -> &'static str {
+        use ast::Operator::*;
+        match op {
+            Add => "+",
+            Sub => "-",
+            Mult => "*",
+            MatMult => "@",
+            Div => "/",
+            Mod => "%",
+            Pow => "**",
+            LShift => "<<",
+            RShift => ">>",
+            BitOr => "|",
+            BitXor => "^",
+            BitAnd => "&",
+            FloorDiv => "//",
+        }
+    }
+
+    fn unary_op_str(op: &ast::UnaryOp) -> &'static str {
+        use ast::UnaryOp::*;
+        match op {
+            Invert => "~",
+            Not => "not ",
+            UAdd => "+",
+            USub => "-",
+        }
+    }
+
+    fn bool_op_str(op: &ast::BoolOp) -> &'static str {
+        match op {
+            ast::BoolOp::And => " and ",
+            ast::BoolOp::Or => " or ",
+        }
+    }
+
+    fn cmp_op_str(op: &ast::CmpOp) -> &'static str {
+        use ast::CmpOp::*;
+        match op {
+            Eq => "==",
+            NotEq => "!=",
+            Lt => "<",
+            LtE => "<=",
+            Gt => ">",
+            GtE => ">=",
+            Is => "is",
+            IsNot => "is not",
+            In => "in",
+            NotIn => "not in",
+        }
+    }
+
+    pub(super) struct Unparser {
+        out: String,
+        indent: usize,
+    }
+
+    impl Unparser {
+        pub(super) fn new() -> Self {
+            Self {
+                out: String::new(),
+                indent: 0,
+            }
+        }
+
+        pub(super) fn finish(self) -> String {
+            self.out
+        }
+
+        fn newline_indent(&mut self) {
+            self.out.push('\n');
+            for _ in 0..self.indent {
+                self.out.push_str("    ");
+            }
+        }
+
+        fn block(&mut self, body: &[ast::located::Stmt]) {
+            self.indent += 1;
+            for stmt in body {
+                self.newline_indent();
+                self.unparse_stmt(stmt);
+            }
+            self.indent -= 1;
+        }
+
+        pub(super) fn unparse_mod(&mut self, module: &ast::located::Mod) {
+            match module {
+                ast::located::Mod::Module(m) => {
+                    for (i, stmt) in m.body.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push('\n');
+                        }
+                        self.unparse_stmt(stmt);
+                    }
+                }
+                ast::located::Mod::Interactive(m) => {
+                    for stmt in &m.body {
+                        self.unparse_stmt(stmt);
+                        self.out.push('\n');
+                    }
+                }
+                ast::located::Mod::Expression(m) => {
+                    self.unparse_expr(&m.body, Prec::Tuple);
+                }
+                ast::located::Mod::FunctionType(_) => {
+                    // Only ever produced by `ast.parse(mode="func_type")`, used for PEP 484
+                    // function-type comments -- not something real source ever unparses to.
+                    self.out.push_str("(...) -> ...");
+                }
+            }
+        }
+
+        fn decorators(&mut self, decorator_list: &[ast::located::Expr]) {
+            for dec in decorator_list {
+                self.out.push('@');
+                self.unparse_expr(dec, Prec::Test);
+                self.newline_indent();
+            }
+        }
+
+        fn unparse_args(&mut self, args: &ast::located::Arguments) {
+            let mut first = true;
+            let mut comma = |this: &mut Self| {
+                if !first {
+                    this.out.push_str(", ");
+                }
+                first = false;
+            };
+            let num_posonly = args.posonlyargs.len();
+            for (i, arg) in args.posonlyargs.iter().chain(args.args.iter()).enumerate() {
+                comma(self);
+                self.unparse_arg(arg);
+                let defaults_start =
+                    num_posonly + args.args.len() - args.defaults.len();
+                if i >= defaults_start {
+                    self.out.push('=');
+                    self.unparse_expr(&args.defaults[i - defaults_start], Prec::Test);
+                }
+                if i + 1 == num_posonly {
+                    comma(self);
+                    self.out.push('/');
+                }
+            }
+            if args.vararg.is_some() || !args.kwonlyargs.is_empty() {
+                comma(self);
+                self.out.push('*');
+                if let Some(vararg) = &args.vararg {
+                    self.unparse_arg(vararg);
+                }
+            }
+            for (arg, default) in args.kwonlyargs.iter().zip(args.kw_defaults.iter()) {
+                comma(self);
+                self.unparse_arg(arg);
+                if let Some(default) = default {
+                    self.out.push('=');
+                    self.unparse_expr(default, Prec::Test);
+                }
+            }
+            if let Some(kwarg) = &args.kwarg {
+                comma(self);
+                self.out.push_str("**");
+                self.unparse_arg(kwarg);
+            }
+        }
+
+        fn unparse_arg(&mut self, arg: &ast::located::Arg) {
+            self.out.push_str(arg.arg.as_str());
+            if let Some(annotation) = &arg.annotation {
+                self.out.push_str(": ");
+                self.unparse_expr(annotation, Prec::Test);
+            }
+        }
+
+        fn unparse_stmt(&mut self, stmt: &ast::located::Stmt) {
+            use ast::located::Stmt::*;
+            match stmt {
+                FunctionDef(s) => {
+                    self.decorators(&s.decorator_list);
+                    self.out.push_str("def ");
+                    self.unparse_funcdef_rest(s.name.as_str(), &s.args, &s.returns, &s.body);
+                }
+                AsyncFunctionDef(s) => {
+                    self.decorators(&s.decorator_list);
+                    self.out.push_str("async def ");
+                    self.unparse_funcdef_rest(s.name.as_str(), &s.args, &s.returns, &s.body);
+                }
+                ClassDef(s) => {
+                    self.decorators(&s.decorator_list);
+                    write!(self.out, "class {}", s.name.as_str()).unwrap();
+                    if !s.bases.is_empty() || !s.keywords.is_empty() {
+                        self.out.push('(');
+                        let mut first = true;
+                        for base in &s.bases {
+                            if !first {
+                                self.out.push_str(", ");
+                            }
+                            first = false;
+                            self.unparse_expr(base, Prec::Test);
+                        }
+                        for kw in &s.keywords {
+                            if !first {
+                                self.out.push_str(", ");
+                            }
+                            first = false;
+                            match &kw.arg {
+                                Some(name) => write!(self.out, "{}=", name.as_str()).unwrap(),
+                                None => self.out.push_str("**"),
+                            }
+                            self.unparse_expr(&kw.value, Prec::Test);
+                        }
+                        self.out.push(')');
+                    }
+                    self.out.push(':');
+                    self.block(&s.body);
+                }
+                Return(s) => {
+                    self.out.push_str("return");
+                    if let Some(value) = &s.value {
+                        self.out.push(' ');
+                        self.unparse_expr(value, Prec::Tuple);
+                    }
+                }
+                Delete(s) => {
+                    self.out.push_str("del ");
+                    self.unparse_comma_separated(&s.targets, Prec::Test);
+                }
+                Assign(s) => {
+                    for target in &s.targets {
+                        self.unparse_expr(target, Prec::Tuple);
+                        self.out.push_str(" = ");
+                    }
+                    self.unparse_expr(&s.value, Prec::Tuple);
+                }
+                AugAssign(s) => {
+                    self.unparse_expr(&s.target, Prec::Tuple);
+                    write!(self.out, " {}= ", bin_op_str(&s.op)).unwrap();
+                    self.unparse_expr(&s.value, Prec::Tuple);
+                }
+                AnnAssign(s) => {
+                    let needs_parens = !s.simple;
+                    if needs_parens {
+                        self.out.push('(');
+                    }
+                    self.unparse_expr(&s.target, Prec::Tuple);
+                    if needs_parens {
+                        self.out.push(')');
+                    }
+                    self.out.push_str(": ");
+                    self.unparse_expr(&s.annotation, Prec::Test);
+                    if let Some(value) = &s.value {
+                        self.out.push_str(" = ");
+                        self.unparse_expr(value, Prec::Tuple);
+                    }
+                }
+                For(s) => self.unparse_for(false, s),
+                AsyncFor(s) => self.unparse_for(true, s),
+                While(s) => {
+                    self.out.push_str("while ");
+                    self.unparse_expr(&s.test, Prec::Test);
+                    self.out.push(':');
+                    self.block(&s.body);
+                    self.unparse_orelse(&s.orelse);
+                }
+                If(s) => {
+                    self.out.push_str("if ");
+                    self.unparse_expr(&s.test, Prec::Test);
+                    self.out.push(':');
+                    self.block(&s.body);
+                    self.unparse_if_orelse(&s.orelse);
+                }
+                With(s) => self.unparse_with(false, s),
+                AsyncWith(s) => self.unparse_with(true, s),
+                Raise(s) => {
+                    self.out.push_str("raise");
+                    if let Some(exc) = &s.exc {
+                        self.out.push(' ');
+                        self.unparse_expr(exc, Prec::Test);
+                    }
+                    if let Some(cause) = &s.cause {
+                        self.out.push_str(" from ");
+                        self.unparse_expr(cause, Prec::Test);
+                    }
+                }
+                Try(s) => {
+                    self.out.push_str("try:");
+                    self.block(&s.body);
+                    for handler in &s.handlers {
+                        let ast::located::ExceptHandler::ExceptHandler(h) = handler;
+                        self.newline_indent();
+                        self.out.push_str("except");
+                        if let Some(ty) = &h.type_ {
+                            self.out.push(' ');
+                            self.unparse_expr(ty, Prec::Test);
+                        }
+                        if let Some(name) = &h.name {
+                            write!(self.out, " as {}", name.as_str()).unwrap();
+                        }
+                        self.out.push(':');
+                        self.block(&h.body);
+                    }
+                    self.unparse_orelse(&s.orelse);
+                    if !s.finalbody.is_empty() {
+                        self.newline_indent();
+                        self.out.push_str("finally:");
+                        self.block(&s.finalbody);
+                    }
+                }
+                Assert(s) => {
+                    self.out.push_str("assert ");
+                    self.unparse_expr(&s.test, Prec::Test);
+                    if let Some(msg) = &s.msg {
+                        self.out.push_str(", ");
+                        self.unparse_expr(msg, Prec::Test);
+                    }
+                }
+                Import(s) => {
+                    self.out.push_str("import ");
+                    self.unparse_aliases(&s.names);
+                }
+                ImportFrom(s) => {
+                    self.out.push_str("from ");
+                    for _ in 0..s.level.map(|l| l.to_u32()).unwrap_or(0) {
+                        self.out.push('.');
+                    }
+                    if let Some(module) = &s.module {
+                        self.out.push_str(module.as_str());
+                    }
+                    self.out.push_str(" import ");
+                    self.unparse_aliases(&s.names);
+                }
+                Global(s) => {
+                    self.out.push_str("global ");
+                    self.unparse_names(&s.names);
+                }
+                Nonlocal(s) => {
+                    self.out.push_str("nonlocal ");
+                    self.unparse_names(&s.names);
+                }
+                Expr(s) => self.unparse_expr(&s.value, Prec::Tuple),
+                Pass(_) => self.out.push_str("pass"),
+                Break(_) => self.out.push_str("break"),
+                Continue(_) => self.out.push_str("continue"),
+            }
+        }
+
+        fn unparse_funcdef_rest(
+            &mut self,
+            name: &str,
+            args: &ast::located::Arguments,
+            returns: &Option<Box<ast::located::Expr>>,
+            body: &[ast::located::Stmt],
+        ) {
+            write!(self.out, "{name}(").unwrap();
+            self.unparse_args(args);
+            self.out.push(')');
+            if let Some(returns) = returns {
+                self.out.push_str(" -> ");
+                self.unparse_expr(returns, Prec::Test);
+            }
+            self.out.push(':');
+            self.block(body);
+        }
+
+        fn unparse_for(&mut self, is_async: bool, s: &ast::located::StmtFor) {
+            self.out.push_str(if is_async { "async for " } else { "for " });
+            self.unparse_expr(&s.target, Prec::Tuple);
+            self.out.push_str(" in ");
+            self.unparse_expr(&s.iter, Prec::Tuple);
+            self.out.push(':');
+            self.block(&s.body);
+            self.unparse_orelse(&s.orelse);
+        }
+
+        fn unparse_with(&mut self, is_async: bool, s: &ast::located::StmtWith) {
+            self.out.push_str(if is_async { "async with " } else { "with " });
+            for (i, item) in s.items.iter().enumerate() {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.unparse_expr(&item.context_expr, Prec::Test);
+                if let Some(vars) = &item.optional_vars {
+                    self.out.push_str(" as ");
+                    self.unparse_expr(vars, Prec::Test);
+                }
+            }
+            self.out.push(':');
+            self.block(&s.body);
+        }
+
+        fn unparse_orelse(&mut self, orelse: &[ast::located::Stmt]) {
+            if !orelse.is_empty() {
+                self.newline_indent();
+                self.out.push_str("else:");
+                self.block(orelse);
+            }
+        }
+
+        // `if`/`elif` chains are encoded as a single-statement `orelse` holding a nested `If`;
+        // rendering that as `elif` instead of a nested `else: if` is what CPython's unparser does.
+        fn unparse_if_orelse(&mut self, orelse: &[ast::located::Stmt]) {
+            match orelse {
+                [ast::located::Stmt::If(nested)] => {
+                    self.newline_indent();
+                    self.out.push_str("elif ");
+                    self.unparse_expr(&nested.test, Prec::Test);
+                    self.out.push(':');
+                    self.block(&nested.body);
+                    self.unparse_if_orelse(&nested.orelse);
+                }
+                _ => self.unparse_orelse(orelse),
+            }
+        }
+
+        fn unparse_aliases(&mut self, names: &[ast::located::Alias]) {
+            for (i, alias) in names.iter().enumerate() {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.out.push_str(alias.name.as_str());
+                if let Some(asname) = &alias.asname {
+                    write!(self.out, " as {}", asname.as_str()).unwrap();
+                }
+            }
+        }
+
+        fn unparse_names(&mut self, names: &[ast::Identifier]) {
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.out.push_str(name.as_str());
+            }
+        }
+
+        fn unparse_comma_separated(&mut self, exprs: &[ast::located::Expr], prec: Prec) {
+            for (i, e) in exprs.iter().enumerate() {
+                if i > 0 {
+                    self.out.push_str(", ");
+                }
+                self.unparse_expr(e, prec);
+            }
+        }
+
+        fn unparse_comprehensions(&mut self, generators: &[ast::located::Comprehension]) {
+            for gen in generators {
+                self.out.push_str(if gen.is_async {
+                    " async for "
+                } else {
+                    " for "
+                });
+                self.unparse_expr(&gen.target, Prec::Tuple);
+                self.out.push_str(" in ");
+                self.unparse_expr(&gen.iter, Prec::Or);
+                for if_ in &gen.ifs {
+                    self.out.push_str(" if ");
+                    self.unparse_expr(if_, Prec::Or);
+                }
+            }
+        }
+
+        /// Renders `expr`, parenthesizing it iff its own precedence is looser than the context
+        /// (`parent_prec`) it's being spliced into requires.
+        fn unparse_expr(&mut self, expr: &ast::located::Expr, parent_prec: Prec) {
+            use ast::located::Expr::*;
+            let prec = match expr {
+                BoolOp(e) => {
+                    if matches!(e.op, ast::BoolOp::And) {
+                        Prec::And
+                    } else {
+                        Prec::Or
+                    }
+                }
+                NamedExpr(_) => Prec::NamedExpr,
+                BinOp(e) => bin_op_prec(&e.op),
+                UnaryOp(e) => match e.op {
+                    ast::UnaryOp::Not => Prec::Not,
+                    _ => Prec::Factor,
+                },
+                Lambda(_) => Prec::Lambda,
+                IfExp(_) => Prec::Test,
+                Compare(_) => Prec::Cmp,
+                Await(_) => Prec::Await,
+                Yield(_) | YieldFrom(_) => Prec::Test,
+                Dict(_) | Set(_) | ListComp(_) | SetComp(_) | DictComp(_) | GeneratorExp(_)
+                | Call(_) | FormattedValue(_) | JoinedStr(_) | Constant(_) | Attribute(_)
+                | Subscript(_) | Starred(_) | Name(_) | List(_) | Slice(_) => Prec::Atom,
+                // Weaker than every other precedence (including `Atom`) so the generic
+                // `needs_parens` check below can actually fire for a tuple: a tuple spliced
+                // anywhere *but* a bare statement/target context (which passes `Prec::Tuple`
+                // itself) needs parens, most visibly as a single-argument call `f((1,))` vs.
+                // the unparenthesized `f(1,)`, which is a call with an int argument, not a
+                // 1-tuple one.
+                Tuple(_) => Prec::Tuple,
+            };
+            let needs_parens = prec < parent_prec;
+            if needs_parens {
+                self.out.push('(');
+            }
+            match expr {
+                BoolOp(e) => {
+                    let op_prec = if matches!(e.op, ast::BoolOp::And) {
+                        Prec::And
+                    } else {
+                        Prec::Or
+                    };
+                    for (i, value) in e.values.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(bool_op_str(&e.op));
+                        }
+                        self.unparse_expr(value, op_prec);
+                    }
+                }
+                NamedExpr(e) => {
+                    // A bare `:=` isn't valid as a statement, an assignment value, or another
+                    // `:=`'s value, so it gets parens of its own wherever the caller adds none.
+                    let own_parens = !needs_parens;
+                    if own_parens {
+                        self.out.push('(');
+                    }
+                    self.unparse_expr(&e.target, Prec::Atom);
+                    self.out.push_str(" := ");
+                    self.unparse_expr(&e.value, Prec::NamedExpr);
+                    if own_parens {
+                        self.out.push(')');
+                    }
+                }
+                BinOp(e) => {
+                    let op_prec = bin_op_prec(&e.op);
+                    // All of these operators are left-associative except `**`, which is
+                    // right-associative -- so for `**` the tighter-than-usual side is the left
+                    // operand (`(-2) ** 2` needs parens, `2 ** -2` doesn't) instead of the right.
+                    let (left_prec, right_prec) = if matches!(e.op, ast::Operator::Pow) {
+                        (Prec::Await, Prec::Factor)
+                    } else {
+                        (op_prec, next_tighter(op_prec))
+                    };
+                    self.unparse_expr(&e.left, left_prec);
+                    write!(self.out, " {} ", bin_op_str(&e.op)).unwrap();
+                    self.unparse_expr(&e.right, right_prec);
+                }
+                UnaryOp(e) => {
+                    self.out.push_str(unary_op_str(&e.op));
+                    let operand_prec = if matches!(e.op, ast::UnaryOp::Not) {
+                        Prec::Not
+                    } else {
+                        Prec::Factor
+                    };
+                    self.unparse_expr(&e.operand, operand_prec);
+                }
+                Lambda(e) => {
+                    self.out.push_str("lambda");
+                    if !e.args.args.is_empty()
+                        || !e.args.posonlyargs.is_empty()
+                        || e.args.vararg.is_some()
+                        || !e.args.kwonlyargs.is_empty()
+                        || e.args.kwarg.is_some()
+                    {
+                        self.out.push(' ');
+                        self.unparse_args(&e.args);
+                    }
+                    self.out.push_str(": ");
+                    self.unparse_expr(&e.body, Prec::Test);
+                }
+                IfExp(e) => {
+                    self.unparse_expr(&e.body, Prec::Or);
+                    self.out.push_str(" if ");
+                    self.unparse_expr(&e.test, Prec::Or);
+                    self.out.push_str(" else ");
+                    self.unparse_expr(&e.orelse, Prec::Test);
+                }
+                Dict(e) => {
+                    self.out.push('{');
+                    for (i, (key, value)) in e.keys.iter().zip(e.values.iter()).enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        match key {
+                            Some(key) => {
+                                self.unparse_expr(key, Prec::Test);
+                                self.out.push_str(": ");
+                                self.unparse_expr(value, Prec::Test);
+                            }
+                            None => {
+                                self.out.push_str("**");
+                                self.unparse_expr(value, Prec::BitOr);
+                            }
+                        }
+                    }
+                    self.out.push('}');
+                }
+                Set(e) => {
+                    self.out.push('{');
+                    self.unparse_comma_separated(&e.elts, Prec::Test);
+                    self.out.push('}');
+                }
+                ListComp(e) => {
+                    self.out.push('[');
+                    self.unparse_expr(&e.elt, Prec::Test);
+                    self.unparse_comprehensions(&e.generators);
+                    self.out.push(']');
+                }
+                SetComp(e) => {
+                    self.out.push('{');
+                    self.unparse_expr(&e.elt, Prec::Test);
+                    self.unparse_comprehensions(&e.generators);
+                    self.out.push('}');
+                }
+                DictComp(e) => {
+                    self.out.push('{');
+                    self.unparse_expr(&e.key, Prec::Test);
+                    self.out.push_str(": ");
+                    self.unparse_expr(&e.value, Prec::Test);
+                    self.unparse_comprehensions(&e.generators);
+                    self.out.push('}');
+                }
+                GeneratorExp(e) => {
+                    self.out.push('(');
+                    self.unparse_expr(&e.elt, Prec::Test);
+                    self.unparse_comprehensions(&e.generators);
+                    self.out.push(')');
+                }
+                Await(e) => {
+                    self.out.push_str("await ");
+                    self.unparse_expr(&e.value, Prec::Await);
+                }
+                Yield(e) => {
+                    self.out.push_str("yield");
+                    if let Some(value) = &e.value {
+                        self.out.push(' ');
+                        self.unparse_expr(value, Prec::Test);
+                    }
+                }
+                YieldFrom(e) => {
+                    self.out.push_str("yield from ");
+                    self.unparse_expr(&e.value, Prec::Test);
+                }
+                Compare(e) => {
+                    // Operands bind one level tighter than `Cmp`, so a nested comparison keeps
+                    // its parens instead of turning into a chained one.
+                    self.unparse_expr(&e.left, Prec::BitOr);
+                    for (op, comparator) in e.ops.iter().zip(e.comparators.iter()) {
+                        write!(self.out, " {} ", cmp_op_str(op)).unwrap();
+                        self.unparse_expr(comparator, Prec::BitOr);
+                    }
+                }
+                Call(e) => {
+                    self.unparse_expr(&e.func, Prec::Atom);
+                    self.out.push('(');
+                    let mut first = true;
+                    for arg in &e.args {
+                        if !first {
+                            self.out.push_str(", ");
+                        }
+                        first = false;
+                        if let ast::located::Expr::Starred(s) = arg {
+                            self.out.push('*');
+                            self.unparse_expr(&s.value, Prec::Test);
+                        } else {
+                            self.unparse_expr(arg, Prec::Test);
+                        }
+                    }
+                    for kw in &e.keywords {
+                        if !first {
+                            self.out.push_str(", ");
+                        }
+                        first = false;
+                        match &kw.arg {
+                            Some(name) => write!(self.out, "{}=", name.as_str()).unwrap(),
+                            None => self.out.push_str("**"),
+                        }
+                        self.unparse_expr(&kw.value, Prec::Test);
+                    }
+                    self.out.push(')');
+                }
+                FormattedValue(_) | JoinedStr(_) => self.unparse_fstring(expr),
+                Constant(e) => self.out.push_str(&repr_constant(&e.value)),
+                Attribute(e) => {
+                    // `1.real` would lex as the float `1.` followed by a name, so an int gets parens.
+                    let int_value =
+                        matches!(e.value.as_ref(), Constant(c) if matches!(c.value, ast::Constant::Int(_)));
+                    if int_value {
+                        self.out.push('(');
+                    }
+                    self.unparse_expr(&e.value, Prec::Atom);
+                    if int_value {
+                        self.out.push(')');
+                    }
+                    write!(self.out, ".{}", e.attr.as_str()).unwrap();
+                }
+                Subscript(e) => {
+                    self.unparse_expr(&e.value, Prec::Atom);
+                    self.out.push('[');
+                    self.unparse_expr(&e.slice, Prec::Tuple);
+                    self.out.push(']');
+                }
+                Starred(e) => {
+                    self.out.push('*');
+                    self.unparse_expr(&e.value, Prec::BitOr);
+                }
+                Name(e) => self.out.push_str(e.id.as_str()),
+                List(e) => {
+                    self.out.push('[');
+                    self.unparse_comma_separated(&e.elts, Prec::Test);
+                    self.out.push(']');
+                }
+                Tuple(e) => {
+                    // Every tuple comes out parenthesized: by the caller when `needs_parens` is
+                    // set, otherwise by its own parens here -- including in a bare statement or
+                    // target context, which passes `Prec::Tuple` and so never sets `needs_parens`.
+                    // That keeps an empty tuple as `()` and a single-element one as `(1,)`
+                    // (`f(1,)` is a call with one int argument, not a 1-tuple argument).
+                    let own_parens = !needs_parens;
+                    if own_parens {
+                        self.out.push('(');
+                    }
+                    for (i, elt) in e.elts.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        self.unparse_expr(elt, Prec::Test);
+                    }
+                    if e.elts.len() == 1 {
+                        self.out.push(',');
+                    }
+                    if own_parens {
+                        self.out.push(')');
+                    }
+                }
+                Slice(e) => {
+                    if let Some(lower) = &e.lower {
+                        self.unparse_expr(lower, Prec::Test);
+                    }
+                    self.out.push(':');
+                    if let Some(upper) = &e.upper {
+                        self.unparse_expr(upper, Prec::Test);
+                    }
+                    if let Some(step) = &e.step {
+                        self.out.push(':');
+                        self.unparse_expr(step, Prec::Test);
+                    }
+                }
+            }
+            if needs_parens {
+                self.out.push(')');
+            }
+        }
+
+        fn unparse_fstring(&mut self, expr: &ast::located::Expr) {
+            let quote = fstring_quote(expr);
+            self.out.push('f');
+            self.out.push(quote);
+            self.unparse_fstring_body(expr, quote);
+            self.out.push(quote);
+        }
+
+        fn unparse_fstring_body(&mut self, expr: &ast::located::Expr, quote: char) {
+            match expr {
+                ast::located::Expr::JoinedStr(e) => {
+                    for value in &e.values {
+                        self.unparse_fstring_body(value, quote);
+                    }
+                }
+                ast::located::Expr::FormattedValue(e) => {
+                    self.out.push('{');
+                    self.unparse_expr(&e.value, Prec::Test);
+                    match e.conversion {
+                        ast::ConversionFlag::Str => self.out.push_str("!s"),
+                        ast::ConversionFlag::Repr => self.out.push_str("!r"),
+                        ast::ConversionFlag::Ascii => self.out.push_str("!a"),
+                        ast::ConversionFlag::None => {}
+                    }
+                    if let Some(format_spec) = &e.format_spec {
+                        self.out.push(':');
+                        self.unparse_fstring_body(format_spec, quote);
+                    }
+                    self.out.push('}');
+                }
+                ast::located::Expr::Constant(e) => {
+                    if let ast::Constant::Str(s) = &e.value {
+                        for ch in s.chars() {
+                            match ch {
+                                '{' => self.out.push_str("{{"),
+                                '}' => self.out.push_str("}}"),
+                                '\\' => self.out.push_str("\\\\"),
+                                c if c == quote => {
+                                    self.out.push('\\');
+                                    self.out.push(c);
+                                }
+                                _ => self.out.push(ch),
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Picks the f-string's outer quote the way CPython's `_Unparser` does: prefer `'`, but if any
+    /// interpolated expression's own rendered source would contain a bare `'` (which, unescaped,
+    /// would terminate the f-string early -- e.g. `d['x']` inside `f"{d['x']}"`), switch the outer
+    /// quote to `"` instead. If the expression source contains both quote characters there's no
+    /// single-character fix left, so fall back to `'` (matching `repr_str`'s same tradeoff for
+    /// plain string literals). Literal text portions of the f-string are never a problem since
+    /// [`Unparser::unparse_fstring_body`] always escapes whichever quote ends up chosen.
+    fn fstring_quote(expr: &ast::located::Expr) -> char {
+        fn contains_quote(expr: &ast::located::Expr, quote: char) -> bool {
+            match expr {
+                ast::located::Expr::JoinedStr(e) => e.values.iter().any(|v| contains_quote(v, quote)),
+                ast::located::Expr::FormattedValue(e) => {
+                    let mut sub = Unparser::new();
+                    sub.unparse_expr(&e.value, Prec::Test);
+                    sub.finish().contains(quote)
+                        || e
+                            .format_spec
+                            .as_deref()
+                            .is_some_and(|spec| contains_quote(spec, quote))
+                }
+                _ => false,
+            }
+        }
+        if !contains_quote(expr, '\'') {
+            '\''
+        } else if !contains_quote(expr, '"') {
+            '"'
+        } else {
+            '\''
+        }
+    }
+
+    /// Renders a constant as source that evaluates back to the same value, since that's what
+    /// `ast.unparse` puts literally into the source for `Constant` nodes. Strings pick their quote
+    /// the way CPython's `repr()` does; bytes always use `'`, and floats and complex numbers use
+    /// Rust's float formatting (so `1e20` comes out as `100000000000000000000.0` and `2j` as
+    /// `2.0j`) rather than CPython's exact spelling.
+    fn repr_constant(c: &ast::Constant) -> String {
+        match c {
+            ast::Constant::None => "None".to_owned(),
+            ast::Constant::Bool(true) => "True".to_owned(),
+            ast::Constant::Bool(false) => "False".to_owned(),
+            ast::Constant::Ellipsis => "...".to_owned(),
+            ast::Constant::Int(i) => i.to_string(),
+            ast::Constant::Float(f) => repr_float(*f),
+            ast::Constant::Complex { real, imag } => {
+                if *real == 0.0 {
+                    format!("{}j", repr_float(*imag))
+                } else {
+                    format!("({}+{}j)", repr_float(*real), repr_float(*imag))
+                }
+            }
+            ast::Constant::Str(s) => repr_str(s),
+            ast::Constant::Bytes(b) => repr_bytes(b),
+            ast::Constant::Tuple(elts) => {
+                let rendered: Vec<String> = elts.iter().map(repr_constant).collect();
+                if rendered.len() == 1 {
+                    format!("({},)", rendered[0])
+                } else {
+                    format!("({})", rendered.join(", "))
+                }
+            }
+        }
+    }
+
+    /// Renders a float the way CPython's real unparser does, which special-cases the three values
+    /// whose `repr()` isn't a valid Python float literal on its own: `inf`/`-inf`/`nan` are bare
+    /// undefined names to the parser, not the infinities/NaN they came from, so they're rewritten
+    /// to expressions that evaluate back to the same value (`1e309` overflows to `inf` at parse
+    /// time, and `1e309 - 1e309` is `nan`).
+    fn repr_float(f: f64) -> String {
+        if f.is_nan() {
+            "(1e309-1e309)".to_owned()
+        } else if f == f64::INFINITY {
+            "1e309".to_owned()
+        } else if f == f64::NEG_INFINITY {
+            "-1e309".to_owned()
+        } else if f == f.trunc() && f.is_finite() {
+            format!("{f:.1}")
+        } else {
+            format!("{f}")
+        }
+    }
+
+    /// Picks the same quote CPython's `repr()` does: prefer `'`, switch to `"` only when the
+    /// string contains a `'` but no `"`.
+    fn repr_str(s: &str) -> String {
+        let quote = if s.contains('\'') && !s.contains('"') {
+            '"'
+        } else {
+            '\''
+        };
+        let mut out = String::with_capacity(s.len() + 2);
+        out.push(quote);
+        for ch in s.chars() {
+            match ch {
+                '\\' => out.push_str("\\\\"),
+                c if c == quote => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 || (c as u32) == 0x7f => {
+                    write!(out, "\\x{:02x}", c as u32).unwrap();
+                }
+                c => out.push(c),
+            }
+        }
+        out.push(quote);
+        out
+    }
+
+    fn repr_bytes(b: &[u8]) -> String {
+        let mut out = String::with_capacity(b.len() + 3);
+        out.push_str("b'");
+        for &byte in b {
+            match byte {
+                b'\\' => out.push_str("\\\\"),
+                b'\'' => out.push_str("\\'"),
+                b'\n' => out.push_str("\\n"),
+                b'\r' => out.push_str("\\r"),
+                b'\t' => out.push_str("\\t"),
+                0x20..=0x7e => out.push(byte as char),
+                _ => write!(out, "\\x{byte:02x}").unwrap(),
+            }
+        }
+        out.push('\'');
+        out
+    }
 }
 
 #[cfg(feature = "rustpython-codegen")]
@@ -363,6 +1679,8 @@ pub const PY_COMPILE_FLAG_AST_ONLY: i32 = 0x0400;
 // Caveat emptor: These flags are undocumented on purpose and depending
 // on their effect outside the standard library is **unsupported**.
 const PY_CF_DONT_IMPLY_DEDENT: i32 = 0x200;
+// Used by builtins::compile() to request `ParseOptions::type_comments` above.
+pub const PY_CF_TYPE_COMMENTS: i32 = 0x1000;
 const PY_CF_ALLOW_INCOMPLETE_INPUT: i32 = 0x4000;
 
 // __future__ flags - sync with Lib/__future__.py
@@ -384,6 +1702,7 @@ const CO_FUTURE_ANNOTATIONS: i32 = 0x1000000;
 // Used by builtins::compile() - the summary of all flags
 pub const PY_COMPILE_FLAGS_MASK: i32 = PY_COMPILE_FLAG_AST_ONLY
     | PY_CF_DONT_IMPLY_DEDENT
+    | PY_CF_TYPE_COMMENTS
     | PY_CF_ALLOW_INCOMPLETE_INPUT
     | CO_NESTED
     | CO_GENERATOR_ALLOWED
@@ -401,3 +1720,933 @@ pub fn make_module(vm: &VirtualMachine) -> PyRef<PyModule> {
     r#gen::extend_module_nodes(vm, &module);
     module
 }
+
+/// Builds the `symtable` module. Unlike `_ast` above, the actual `sys.modules["symtable"]`
+/// registration lives in the stdlib module table, not in this file.
+pub fn make_symtable_module(vm: &VirtualMachine) -> PyRef<PyModule> {
+    symtable::make_module(vm)
+}
+
+/// Static name-resolution analysis over the AST produced by this module's `Node::ast_from_object`
+/// conversions, exposed as the `symtable` standard module (mirrors CPython's `Lib/symtable.py` /
+/// `Python/symtable.c`).
+///
+/// The algorithm is the textbook two-pass one, driven by `binding::build_and_resolve`: a first
+/// pass walks the tree once per scope collecting every name that scope binds (assignment targets,
+/// `def`/`class` names, parameters, imports, `for` targets, walrus targets) and every name it
+/// merely references, plus explicit `global`/`nonlocal` declarations; a second pass then walks the
+/// resulting scope tree again, resolving each referenced-but-unbound name against the enclosing
+/// scope chain and classifying it as local, free, cell, or global (explicit or implicit).
+///
+/// Two invariants CPython's own symtable documents are preserved here: class scopes are skipped
+/// when resolving a nested function's free variables (a method cannot see its class's own
+/// attributes as free variables the way a nested function can see an outer function's locals),
+/// and each comprehension (except the expression for its leftmost `for`'s iterable, which runs in
+/// the enclosing scope) gets its own scope.
+///
+/// Scope: this does not attempt real builtin-name detection (CPython consults the actual
+/// `builtins` module); any name that resolves to nothing in the entire enclosing scope chain is
+/// classified as global-implicit rather than distinguishing "assumed builtin" from "assumed
+/// module global", the same bounded simplification this crate has made elsewhere for pieces that
+/// would otherwise require state unavailable to a pure AST pass. PEP 695 type-parameter scopes
+/// are not modeled, since the AST produced by this module doesn't carry `type_params`.
+#[pymodule]
+mod symtable {
+    use super::binding;
+    use crate::{
+        PyObjectRef, PyPayload, PyResult, VirtualMachine, builtins::PyStrRef,
+        convert::ToPyException,
+    };
+    #[cfg(feature = "rustpython-parser")]
+    use rustpython_ast::fold::Fold;
+    use std::cell::RefCell;
+
+    #[pyattr]
+    #[pyclass(module = "symtable", name = "SymbolTable")]
+    #[derive(Debug, PyPayload)]
+    pub(crate) struct PySymbolTable {
+        scope: RefCell<binding::ResolvedScope>,
+    }
+
+    #[pyclass]
+    impl PySymbolTable {
+        #[pymethod]
+        fn get_type(&self) -> String {
+            self.scope.borrow().kind.as_str().to_owned()
+        }
+
+        #[pymethod]
+        fn get_name(&self) -> String {
+            self.scope.borrow().name.clone()
+        }
+
+        #[pymethod]
+        fn get_lineno(&self) -> u32 {
+            self.scope.borrow().lineno
+        }
+
+        #[pymethod]
+        fn is_optimized(&self) -> bool {
+            matches!(
+                self.scope.borrow().kind,
+                binding::ScopeKind::Function | binding::ScopeKind::Lambda
+            )
+        }
+
+        #[pymethod]
+        fn is_nested(&self) -> bool {
+            self.scope.borrow().is_nested
+        }
+
+        #[pymethod]
+        fn has_children(&self) -> bool {
+            !self.scope.borrow().children.is_empty()
+        }
+
+        #[pymethod]
+        fn get_identifiers(&self) -> Vec<String> {
+            self.scope
+                .borrow()
+                .symbols
+                .iter()
+                .map(|sym| sym.name.clone())
+                .collect()
+        }
+
+        #[pymethod]
+        fn get_symbols(&self, vm: &VirtualMachine) -> Vec<PyObjectRef> {
+            self.scope
+                .borrow()
+                .symbols
+                .iter()
+                .cloned()
+                .map(|sym| PySymbol { sym }.into_ref(&vm.ctx).into())
+                .collect()
+        }
+
+        #[pymethod]
+        fn lookup(&self, name: PyStrRef, vm: &VirtualMachine) -> PyResult<PyObjectRef> {
+            let scope = self.scope.borrow();
+            scope
+                .symbols
+                .iter()
+                .find(|sym| sym.name == name.as_str())
+                .cloned()
+                .map(|sym| PySymbol { sym }.into_ref(&vm.ctx).into())
+                .ok_or_else(|| {
+                    vm.new_lookup_error(format!("lookup {} failed", name.as_str()))
+                })
+        }
+
+        #[pymethod]
+        fn get_children(&self, vm: &VirtualMachine) -> Vec<PyObjectRef> {
+            self.scope
+                .borrow()
+                .children
+                .iter()
+                .cloned()
+                .map(|scope| {
+                    PySymbolTable {
+                        scope: RefCell::new(scope),
+                    }
+                    .into_ref(&vm.ctx)
+                    .into()
+                })
+                .collect()
+        }
+    }
+
+    #[pyattr]
+    #[pyclass(module = "symtable", name = "Symbol")]
+    #[derive(Debug, PyPayload)]
+    pub(crate) struct PySymbol {
+        sym: binding::ResolvedSymbol,
+    }
+
+    #[pyclass]
+    impl PySymbol {
+        #[pymethod]
+        fn get_name(&self) -> String {
+            self.sym.name.clone()
+        }
+
+        #[pymethod]
+        fn is_referenced(&self) -> bool {
+            self.sym.binding.referenced
+        }
+
+        #[pymethod]
+        fn is_parameter(&self) -> bool {
+            self.sym.binding.parameter
+        }
+
+        #[pymethod]
+        fn is_imported(&self) -> bool {
+            self.sym.binding.imported
+        }
+
+        #[pymethod]
+        fn is_annotated(&self) -> bool {
+            self.sym.binding.annotated
+        }
+
+        #[pymethod]
+        fn is_local(&self) -> bool {
+            matches!(
+                self.sym.classification,
+                binding::Classification::Local | binding::Classification::Cell
+            )
+        }
+
+        #[pymethod]
+        fn is_global(&self) -> bool {
+            matches!(
+                self.sym.classification,
+                binding::Classification::GlobalExplicit | binding::Classification::GlobalImplicit
+            )
+        }
+
+        #[pymethod]
+        fn is_declared_global(&self) -> bool {
+            matches!(
+                self.sym.classification,
+                binding::Classification::GlobalExplicit
+            )
+        }
+
+        #[pymethod]
+        fn is_free(&self) -> bool {
+            matches!(self.sym.classification, binding::Classification::Free)
+        }
+
+        #[pymethod]
+        fn is_assigned(&self) -> bool {
+            self.sym.binding.assigned
+        }
+
+        #[pymethod]
+        fn is_namespace(&self) -> bool {
+            self.sym.is_also_child_scope_name
+        }
+    }
+
+    /// `symtable.symtable(code, filename, compile_type)`: parses `code` and returns the root
+    /// [`PySymbolTable`] for the resulting module/expression/interactive top-level scope.
+    #[cfg(feature = "rustpython-parser")]
+    #[pyfunction]
+    fn symtable(
+        code: PyStrRef,
+        _filename: PyStrRef,
+        compile_type: PyStrRef,
+        vm: &VirtualMachine,
+    ) -> PyResult<PyObjectRef> {
+        let mode = match compile_type.as_str() {
+            "exec" => super::parser::Mode::Module,
+            "eval" => super::parser::Mode::Expression,
+            "single" => super::parser::Mode::Interactive,
+            other => {
+                return Err(vm.new_value_error(format!("unsupported compile_type {other:?}")));
+            }
+        };
+        let mut locator = super::LinearLocator::new(code.as_str());
+        let top = super::parser::parse(code.as_str(), mode, "<string>")
+            .map_err(|e| locator.locate_error(e))
+            .map_err(|e| (e, None).to_pyexception(vm))?;
+        let top = locator.fold_mod(top).unwrap();
+        let root = binding::build_and_resolve(&top);
+        Ok(PySymbolTable {
+            scope: RefCell::new(root),
+        }
+        .into_ref(&vm.ctx)
+        .into())
+    }
+}
+
+/// The binding-analysis engine backing the `symtable` module above. Kept free of any `PyObject`
+/// dependency so it can be unit-reasoned-about purely in terms of the AST.
+mod binding {
+    use super::ast;
+
+    /// Matches `symtable.SymbolTable.get_type()`'s string values.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(crate) enum ScopeKind {
+        Module,
+        Function,
+        Class,
+        Lambda,
+        /// Reported as `"function"` by `get_type()`, like CPython's does, but tracked distinctly
+        /// here since comprehensions have their own scoping quirks (the leftmost `for`'s iterable
+        /// is evaluated in the enclosing scope, and walrus targets hoist past them).
+        Comprehension,
+    }
+
+    impl ScopeKind {
+        pub(crate) fn as_str(self) -> &'static str {
+            match self {
+                ScopeKind::Module => "module",
+                ScopeKind::Function | ScopeKind::Lambda | ScopeKind::Comprehension => "function",
+                ScopeKind::Class => "class",
+            }
+        }
+    }
+
+    /// How a name resolved against the enclosing scope chain -- CPython's `is_local`/`is_free`/
+    /// `is_global`/etc. on `Symbol` are all derived from this one classification.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub(crate) enum Classification {
+        Local,
+        /// Local to this scope, but also referenced as a free variable by a nested scope -- what
+        /// CPython calls a cell variable.
+        Cell,
+        Free,
+        GlobalExplicit,
+        GlobalImplicit,
+    }
+
+    /// Per-name bookkeeping collected while walking a scope's body, before the name is
+    /// classified.
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct BindingInfo {
+        pub(crate) assigned: bool,
+        pub(crate) parameter: bool,
+        pub(crate) imported: bool,
+        pub(crate) global_explicit: bool,
+        pub(crate) nonlocal_explicit: bool,
+        pub(crate) referenced: bool,
+        pub(crate) annotated: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct ResolvedSymbol {
+        pub(crate) name: String,
+        pub(crate) binding: BindingInfo,
+        pub(crate) classification: Classification,
+        /// True if a child scope shares this exact name as its own scope name (e.g. a nested
+        /// `def`/`class` of the same name) -- used for `Symbol.is_namespace()`, matching
+        /// CPython's "a namespace symbol names a scope binding" semantics loosely.
+        pub(crate) is_also_child_scope_name: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct ResolvedScope {
+        pub(crate) name: String,
+        pub(crate) kind: ScopeKind,
+        pub(crate) lineno: u32,
+        pub(crate) is_nested: bool,
+        pub(crate) symbols: Vec<ResolvedSymbol>,
+        pub(crate) children: Vec<ResolvedScope>,
+    }
+
+    /// A scope still being built: bindings/references collected in source order, matching
+    /// CPython's own symtable insertion order for `get_identifiers()`.
+    struct ScopeBuilder {
+        name: String,
+        kind: ScopeKind,
+        lineno: u32,
+        bindings: Vec<(String, BindingInfo)>,
+        children: Vec<ScopeBuilder>,
+    }
+
+    impl ScopeBuilder {
+        fn new(name: impl Into<String>, kind: ScopeKind, lineno: u32) -> Self {
+            Self {
+                name: name.into(),
+                kind,
+                lineno,
+                bindings: Vec::new(),
+                children: Vec::new(),
+            }
+        }
+
+        fn binding_mut(&mut self, name: &str) -> &mut BindingInfo {
+            if let Some(idx) = self.bindings.iter().position(|(n, _)| n == name) {
+                &mut self.bindings[idx].1
+            } else {
+                self.bindings.push((name.to_owned(), BindingInfo::default()));
+                &mut self.bindings.last_mut().unwrap().1
+            }
+        }
+    }
+
+    /// Where a walrus (`:=`) target inside a comprehension needs to end up: PEP 572 says it binds
+    /// in the nearest enclosing scope that isn't itself a comprehension, so each comprehension
+    /// scope collects its own targets here and bubbles them up through its parent's own `hoist`
+    /// list until a non-comprehension scope absorbs them.
+    type WalrusHoist = Vec<String>;
+
+    pub(crate) fn build_and_resolve(module: &ast::located::Mod) -> ResolvedScope {
+        let root_builder = build_module(module);
+        let mut arena = Vec::new();
+        let root_idx = flatten(&mut arena, root_builder);
+        resolve_free(&mut arena, root_idx, &[]);
+        to_resolved(&arena, root_idx, false)
+    }
+
+    fn build_module(module: &ast::located::Mod) -> ScopeBuilder {
+        let mut scope = ScopeBuilder::new("top", ScopeKind::Module, 0);
+        let mut hoist = WalrusHoist::new();
+        match module {
+            ast::located::Mod::Module(m) => visit_stmts(&mut scope, &mut hoist, &m.body),
+            ast::located::Mod::Interactive(m) => visit_stmts(&mut scope, &mut hoist, &m.body),
+            ast::located::Mod::Expression(m) => visit_expr(&mut scope, &mut hoist, &m.body),
+            ast::located::Mod::FunctionType(_) => {}
+        }
+        absorb(&mut scope, hoist);
+        scope
+    }
+
+    fn absorb(scope: &mut ScopeBuilder, hoist: WalrusHoist) {
+        for name in hoist {
+            let b = scope.binding_mut(&name);
+            b.assigned = true;
+        }
+    }
+
+    fn visit_stmts(scope: &mut ScopeBuilder, hoist: &mut WalrusHoist, body: &[ast::located::Stmt]) {
+        for stmt in body {
+            visit_stmt(scope, hoist, stmt);
+        }
+    }
+
+    fn bind_target(scope: &mut ScopeBuilder, hoist: &mut WalrusHoist, target: &ast::located::Expr) {
+        match target {
+            ast::located::Expr::Name(e) => scope.binding_mut(e.id.as_str()).assigned = true,
+            ast::located::Expr::Tuple(e) => {
+                for elt in &e.elts {
+                    bind_target(scope, hoist, elt);
+                }
+            }
+            ast::located::Expr::List(e) => {
+                for elt in &e.elts {
+                    bind_target(scope, hoist, elt);
+                }
+            }
+            ast::located::Expr::Starred(e) => bind_target(scope, hoist, &e.value),
+            // `a.b = x` / `a[b] = x`: the attribute/subscript target isn't a new binding, but its
+            // own subexpressions are still references.
+            ast::located::Expr::Attribute(e) => visit_expr(scope, hoist, &e.value),
+            ast::located::Expr::Subscript(e) => {
+                visit_expr(scope, hoist, &e.value);
+                visit_expr(scope, hoist, &e.slice);
+            }
+            other => visit_expr(scope, hoist, other),
+        }
+    }
+
+    fn visit_stmt(scope: &mut ScopeBuilder, hoist: &mut WalrusHoist, stmt: &ast::located::Stmt) {
+        use ast::located::Stmt::*;
+        match stmt {
+            FunctionDef(s) | AsyncFunctionDef(s) => {
+                scope.binding_mut(s.name.as_str()).assigned = true;
+                for dec in &s.decorator_list {
+                    visit_expr(scope, hoist, dec);
+                }
+                for default in s.args.defaults.iter().chain(
+                    s.args
+                        .kw_defaults
+                        .iter()
+                        .filter_map(|d| d.as_ref()),
+                ) {
+                    visit_expr(scope, hoist, default);
+                }
+                for ann in all_arg_annotations(&s.args) {
+                    visit_expr(scope, hoist, ann);
+                }
+                if let Some(returns) = &s.returns {
+                    visit_expr(scope, hoist, returns);
+                }
+                let child =
+                    build_function(s.name.as_str(), ScopeKind::Function, &s.args, &s.body);
+                scope.children.push(child);
+            }
+            ClassDef(s) => {
+                scope.binding_mut(s.name.as_str()).assigned = true;
+                for dec in &s.decorator_list {
+                    visit_expr(scope, hoist, dec);
+                }
+                for base in &s.bases {
+                    visit_expr(scope, hoist, base);
+                }
+                for kw in &s.keywords {
+                    visit_expr(scope, hoist, &kw.value);
+                }
+                let mut child = ScopeBuilder::new(s.name.as_str(), ScopeKind::Class, 0);
+                let mut child_hoist = WalrusHoist::new();
+                visit_stmts(&mut child, &mut child_hoist, &s.body);
+                absorb(&mut child, child_hoist);
+                scope.children.push(child);
+            }
+            Return(s) => {
+                if let Some(value) = &s.value {
+                    visit_expr(scope, hoist, value);
+                }
+            }
+            Delete(s) => {
+                for target in &s.targets {
+                    if let ast::located::Expr::Name(e) = target {
+                        scope.binding_mut(e.id.as_str()).assigned = true;
+                    } else {
+                        visit_expr(scope, hoist, target);
+                    }
+                }
+            }
+            Assign(s) => {
+                visit_expr(scope, hoist, &s.value);
+                for target in &s.targets {
+                    bind_target(scope, hoist, target);
+                }
+            }
+            AugAssign(s) => {
+                visit_expr(scope, hoist, &s.value);
+                bind_target(scope, hoist, &s.target);
+            }
+            AnnAssign(s) => {
+                visit_expr(scope, hoist, &s.annotation);
+                if let Some(value) = &s.value {
+                    visit_expr(scope, hoist, value);
+                }
+                if let ast::located::Expr::Name(e) = s.target.as_ref() {
+                    let b = scope.binding_mut(e.id.as_str());
+                    b.annotated = true;
+                    if s.value.is_some() {
+                        b.assigned = true;
+                    }
+                } else {
+                    bind_target(scope, hoist, &s.target);
+                }
+            }
+            For(s) | AsyncFor(s) => {
+                visit_expr(scope, hoist, &s.iter);
+                bind_target(scope, hoist, &s.target);
+                visit_stmts(scope, hoist, &s.body);
+                visit_stmts(scope, hoist, &s.orelse);
+            }
+            While(s) => {
+                visit_expr(scope, hoist, &s.test);
+                visit_stmts(scope, hoist, &s.body);
+                visit_stmts(scope, hoist, &s.orelse);
+            }
+            If(s) => {
+                visit_expr(scope, hoist, &s.test);
+                visit_stmts(scope, hoist, &s.body);
+                visit_stmts(scope, hoist, &s.orelse);
+            }
+            With(s) | AsyncWith(s) => {
+                for item in &s.items {
+                    visit_expr(scope, hoist, &item.context_expr);
+                    if let Some(vars) = &item.optional_vars {
+                        bind_target(scope, hoist, vars);
+                    }
+                }
+                visit_stmts(scope, hoist, &s.body);
+            }
+            Raise(s) => {
+                if let Some(exc) = &s.exc {
+                    visit_expr(scope, hoist, exc);
+                }
+                if let Some(cause) = &s.cause {
+                    visit_expr(scope, hoist, cause);
+                }
+            }
+            Try(s) => {
+                visit_stmts(scope, hoist, &s.body);
+                for handler in &s.handlers {
+                    let ast::located::ExceptHandler::ExceptHandler(h) = handler;
+                    if let Some(ty) = &h.type_ {
+                        visit_expr(scope, hoist, ty);
+                    }
+                    if let Some(name) = &h.name {
+                        scope.binding_mut(name.as_str()).assigned = true;
+                    }
+                    visit_stmts(scope, hoist, &h.body);
+                }
+                visit_stmts(scope, hoist, &s.orelse);
+                visit_stmts(scope, hoist, &s.finalbody);
+            }
+            Assert(s) => {
+                visit_expr(scope, hoist, &s.test);
+                if let Some(msg) = &s.msg {
+                    visit_expr(scope, hoist, msg);
+                }
+            }
+            Import(s) => {
+                for alias in &s.names {
+                    let bound = alias
+                        .asname
+                        .as_ref()
+                        .map(|n| n.as_str())
+                        .unwrap_or_else(|| alias.name.as_str().split('.').next().unwrap());
+                    let b = scope.binding_mut(bound);
+                    b.assigned = true;
+                    b.imported = true;
+                }
+            }
+            ImportFrom(s) => {
+                for alias in &s.names {
+                    if alias.name.as_str() == "*" {
+                        // `from x import *`: conservatively don't model the injected names --
+                        // CPython's own symtable marks the scope optimized=False for this reason.
+                        continue;
+                    }
+                    let bound = alias
+                        .asname
+                        .as_ref()
+                        .map(|n| n.as_str())
+                        .unwrap_or_else(|| alias.name.as_str());
+                    let b = scope.binding_mut(bound);
+                    b.assigned = true;
+                    b.imported = true;
+                }
+            }
+            Global(s) => {
+                for name in &s.names {
+                    scope.binding_mut(name.as_str()).global_explicit = true;
+                }
+            }
+            Nonlocal(s) => {
+                for name in &s.names {
+                    scope.binding_mut(name.as_str()).nonlocal_explicit = true;
+                }
+            }
+            Expr(s) => visit_expr(scope, hoist, &s.value),
+            Pass(_) | Break(_) | Continue(_) => {}
+        }
+    }
+
+    fn all_arg_annotations(args: &ast::located::Arguments) -> Vec<&ast::located::Expr> {
+        args.posonlyargs
+            .iter()
+            .chain(args.args.iter())
+            .chain(args.kwonlyargs.iter())
+            .chain(args.vararg.iter().map(|a| a.as_ref()))
+            .chain(args.kwarg.iter().map(|a| a.as_ref()))
+            .filter_map(|arg| arg.annotation.as_deref())
+            .collect()
+    }
+
+    fn bind_params(scope: &mut ScopeBuilder, args: &ast::located::Arguments) {
+        for arg in args
+            .posonlyargs
+            .iter()
+            .chain(args.args.iter())
+            .chain(args.kwonlyargs.iter())
+            .chain(args.vararg.iter().map(|a| a.as_ref()))
+            .chain(args.kwarg.iter().map(|a| a.as_ref()))
+        {
+            scope.binding_mut(arg.arg.as_str()).parameter = true;
+        }
+    }
+
+    fn build_function(
+        name: &str,
+        kind: ScopeKind,
+        args: &ast::located::Arguments,
+        body: &[ast::located::Stmt],
+    ) -> ScopeBuilder {
+        let mut scope = ScopeBuilder::new(name, kind, 0);
+        bind_params(&mut scope, args);
+        let mut hoist = WalrusHoist::new();
+        visit_stmts(&mut scope, &mut hoist, body);
+        absorb(&mut scope, hoist);
+        scope
+    }
+
+    fn build_comprehension_scope(
+        elt_exprs: &[&ast::located::Expr],
+        generators: &[ast::located::Comprehension],
+    ) -> (ScopeBuilder, WalrusHoist) {
+        let mut scope = ScopeBuilder::new("<comprehension>", ScopeKind::Comprehension, 0);
+        let mut hoist = WalrusHoist::new();
+        for (i, gen) in generators.iter().enumerate() {
+            bind_target(&mut scope, &mut hoist, &gen.target);
+            // The leftmost generator's iterable is evaluated in the *enclosing* scope by the
+            // caller, not here; every other generator's iterable (and all `if` filters) run in
+            // this comprehension's own scope.
+            if i > 0 {
+                visit_expr(&mut scope, &mut hoist, &gen.iter);
+            }
+            for if_ in &gen.ifs {
+                visit_expr(&mut scope, &mut hoist, if_);
+            }
+        }
+        for elt in elt_exprs {
+            visit_expr(&mut scope, &mut hoist, elt);
+        }
+        (scope, hoist)
+    }
+
+    fn visit_comprehension(
+        scope: &mut ScopeBuilder,
+        hoist: &mut WalrusHoist,
+        elt_exprs: &[&ast::located::Expr],
+        generators: &[ast::located::Comprehension],
+    ) {
+        if let Some(first) = generators.first() {
+            visit_expr(scope, hoist, &first.iter);
+        }
+        let (child, bubbled) = build_comprehension_scope(elt_exprs, generators);
+        hoist.extend(bubbled);
+        scope.children.push(child);
+    }
+
+    fn visit_expr(scope: &mut ScopeBuilder, hoist: &mut WalrusHoist, expr: &ast::located::Expr) {
+        use ast::located::Expr::*;
+        match expr {
+            BoolOp(e) => {
+                for v in &e.values {
+                    visit_expr(scope, hoist, v);
+                }
+            }
+            NamedExpr(e) => {
+                visit_expr(scope, hoist, &e.value);
+                if let ast::located::Expr::Name(target) = e.target.as_ref() {
+                    hoist.push(target.id.as_str().to_owned());
+                } else {
+                    bind_target(scope, hoist, &e.target);
+                }
+            }
+            BinOp(e) => {
+                visit_expr(scope, hoist, &e.left);
+                visit_expr(scope, hoist, &e.right);
+            }
+            UnaryOp(e) => visit_expr(scope, hoist, &e.operand),
+            Lambda(e) => {
+                for default in e.args.defaults.iter().chain(
+                    e.args
+                        .kw_defaults
+                        .iter()
+                        .filter_map(|d| d.as_ref()),
+                ) {
+                    visit_expr(scope, hoist, default);
+                }
+                let child = build_function("<lambda>", ScopeKind::Lambda, &e.args, &[]).tap_body(
+                    |child| {
+                        let mut lambda_hoist = WalrusHoist::new();
+                        visit_expr(child, &mut lambda_hoist, &e.body);
+                        absorb(child, lambda_hoist);
+                    },
+                );
+                scope.children.push(child);
+            }
+            IfExp(e) => {
+                visit_expr(scope, hoist, &e.test);
+                visit_expr(scope, hoist, &e.body);
+                visit_expr(scope, hoist, &e.orelse);
+            }
+            Dict(e) => {
+                for (key, value) in e.keys.iter().zip(e.values.iter()) {
+                    if let Some(key) = key {
+                        visit_expr(scope, hoist, key);
+                    }
+                    visit_expr(scope, hoist, value);
+                }
+            }
+            Set(e) => {
+                for elt in &e.elts {
+                    visit_expr(scope, hoist, elt);
+                }
+            }
+            ListComp(e) => visit_comprehension(scope, hoist, &[&e.elt], &e.generators),
+            SetComp(e) => visit_comprehension(scope, hoist, &[&e.elt], &e.generators),
+            GeneratorExp(e) => visit_comprehension(scope, hoist, &[&e.elt], &e.generators),
+            DictComp(e) => visit_comprehension(scope, hoist, &[&e.key, &e.value], &e.generators),
+            Await(e) => visit_expr(scope, hoist, &e.value),
+            Yield(e) => {
+                if let Some(value) = &e.value {
+                    visit_expr(scope, hoist, value);
+                }
+            }
+            YieldFrom(e) => visit_expr(scope, hoist, &e.value),
+            Compare(e) => {
+                visit_expr(scope, hoist, &e.left);
+                for c in &e.comparators {
+                    visit_expr(scope, hoist, c);
+                }
+            }
+            Call(e) => {
+                visit_expr(scope, hoist, &e.func);
+                for arg in &e.args {
+                    visit_expr(scope, hoist, arg);
+                }
+                for kw in &e.keywords {
+                    visit_expr(scope, hoist, &kw.value);
+                }
+            }
+            FormattedValue(e) => visit_expr(scope, hoist, &e.value),
+            JoinedStr(e) => {
+                for value in &e.values {
+                    visit_expr(scope, hoist, value);
+                }
+            }
+            Constant(_) => {}
+            Attribute(e) => visit_expr(scope, hoist, &e.value),
+            Subscript(e) => {
+                visit_expr(scope, hoist, &e.value);
+                visit_expr(scope, hoist, &e.slice);
+            }
+            Starred(e) => visit_expr(scope, hoist, &e.value),
+            Name(e) => scope.binding_mut(e.id.as_str()).referenced = true,
+            List(e) => {
+                for elt in &e.elts {
+                    visit_expr(scope, hoist, elt);
+                }
+            }
+            Tuple(e) => {
+                for elt in &e.elts {
+                    visit_expr(scope, hoist, elt);
+                }
+            }
+            Slice(e) => {
+                if let Some(lower) = &e.lower {
+                    visit_expr(scope, hoist, lower);
+                }
+                if let Some(upper) = &e.upper {
+                    visit_expr(scope, hoist, upper);
+                }
+                if let Some(step) = &e.step {
+                    visit_expr(scope, hoist, step);
+                }
+            }
+        }
+    }
+
+    impl ScopeBuilder {
+        /// Small helper so [`visit_expr`]'s `Lambda` arm can build the child scope and then keep
+        /// populating it (the lambda body is a single expression, not a `body: &[Stmt]` like every
+        /// other function-like scope, so it can't go through [`build_function`] directly).
+        fn tap_body(mut self, f: impl FnOnce(&mut Self)) -> Self {
+            f(&mut self);
+            self
+        }
+    }
+
+    /// A flattened, arena-indexed copy of a [`ScopeBuilder`] tree. Resolution needs to record,
+    /// for a scope, which of *its own* locally-bound names a descendant captured as free (so that
+    /// name can be reported as a cell variable) -- working from indices into a flat `Vec` rather
+    /// than the original owned tree sidesteps the aliasing that would otherwise require, since an
+    /// ancestor and its descendant would need to be mutated independently during the same walk.
+    struct ScopeNode {
+        name: String,
+        kind: ScopeKind,
+        lineno: u32,
+        bindings: Vec<(String, BindingInfo)>,
+        children: Vec<usize>,
+        /// Names resolved as free variables (bound in some ancestor) during [`resolve_free`].
+        resolved_free: std::collections::HashSet<String>,
+        /// Names local to this scope that some descendant scope captured as a free variable --
+        /// these become cell variables.
+        captured: std::collections::HashSet<String>,
+    }
+
+    fn flatten(arena: &mut Vec<ScopeNode>, builder: ScopeBuilder) -> usize {
+        let ScopeBuilder {
+            name,
+            kind,
+            lineno,
+            bindings,
+            children,
+        } = builder;
+        let idx = arena.len();
+        arena.push(ScopeNode {
+            name,
+            kind,
+            lineno,
+            bindings,
+            children: Vec::new(),
+            resolved_free: std::collections::HashSet::new(),
+            captured: std::collections::HashSet::new(),
+        });
+        let child_indices: Vec<usize> = children.into_iter().map(|c| flatten(arena, c)).collect();
+        arena[idx].children = child_indices;
+        idx
+    }
+
+    fn is_locally_bound(b: &BindingInfo) -> bool {
+        b.assigned || b.parameter || b.imported
+    }
+
+    /// Resolves every scope's unbound references against `ancestors` (indices of the chain of
+    /// enclosing scopes, outermost first, *excluding* the module scope, whose names are globals
+    /// rather than free variables, and any `Class` scope -- a method can't see its class's
+    /// attributes as free variables the way a nested function can see an outer function's
+    /// locals). A name found in the nearest qualifying ancestor is recorded as free here and as
+    /// captured on that ancestor; a name not found anywhere in the chain is left unresolved and
+    /// later classified as global-implicit.
+    fn resolve_free(arena: &mut [ScopeNode], idx: usize, ancestors: &[usize]) {
+        let mut own_chain = ancestors.to_vec();
+        if !matches!(arena[idx].kind, ScopeKind::Class | ScopeKind::Module) {
+            own_chain.push(idx);
+        }
+        let children = arena[idx].children.clone();
+        for child in children {
+            resolve_free(arena, child, &own_chain);
+        }
+
+        let to_resolve: Vec<String> = arena[idx]
+            .bindings
+            .iter()
+            .filter(|(_, b)| {
+                b.referenced && !is_locally_bound(b) && !b.global_explicit && !b.nonlocal_explicit
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in to_resolve {
+            let found = ancestors.iter().rev().find(|&&a| {
+                arena[a]
+                    .bindings
+                    .iter()
+                    .any(|(n, b)| n == &name && is_locally_bound(b))
+            });
+            if let Some(&ancestor) = found {
+                arena[idx].resolved_free.insert(name.clone());
+                arena[ancestor].captured.insert(name);
+            }
+        }
+    }
+
+    fn to_resolved(arena: &[ScopeNode], idx: usize, is_nested: bool) -> ResolvedScope {
+        let node = &arena[idx];
+        let child_names: std::collections::HashSet<&str> =
+            node.children.iter().map(|&c| arena[c].name.as_str()).collect();
+        let symbols = node
+            .bindings
+            .iter()
+            .map(|(name, binding)| {
+                let classification = if binding.global_explicit {
+                    Classification::GlobalExplicit
+                } else if binding.nonlocal_explicit {
+                    Classification::Free
+                } else if is_locally_bound(binding) {
+                    if node.captured.contains(name) {
+                        Classification::Cell
+                    } else {
+                        Classification::Local
+                    }
+                } else if node.resolved_free.contains(name) {
+                    Classification::Free
+                } else {
+                    Classification::GlobalImplicit
+                };
+                ResolvedSymbol {
+                    name: name.clone(),
+                    binding: binding.clone(),
+                    classification,
+                    is_also_child_scope_name: child_names.contains(name.as_str()),
+                }
+            })
+            .collect();
+        let children = node
+            .children
+            .iter()
+            .map(|&c| to_resolved(arena, c, true))
+            .collect();
+        ResolvedScope {
+            name: node.name.clone(),
+            kind: node.kind,
+            lineno: node.lineno,
+            is_nested,
+            symbols,
+            children,
+        }
+    }
+}